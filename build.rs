@@ -0,0 +1,36 @@
+use std::{path::Path, process::Command};
+
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+// Record the commit being built, so that run manifests can say where outputs came from
+fn main() {
+    let git_commit = match git(&["rev-parse", "HEAD"]) {
+        Some(commit) => match git(&["status", "--porcelain"]) {
+            Some(status) if !status.is_empty() => format!("{}-dirty", commit),
+            _ => commit,
+        },
+        None => "unknown".to_string(),
+    };
+    println!("cargo:rustc-env=GIT_COMMIT={}", git_commit);
+
+    // Rerun on new commits, whether HEAD moves or the branch it points to does, and on edits
+    // to the sources, which would make the tree dirty
+    let head_ref =
+        git(&["symbolic-ref", "-q", "HEAD"]).map(|head_ref| format!(".git/{}", head_ref));
+    let git_files = [".git/HEAD", ".git/index", ".git/packed-refs"];
+    // Missing files count as changed, which would rerun the script on every build
+    for git_file in git_files.iter().copied().chain(head_ref.as_deref()) {
+        if Path::new(git_file).exists() {
+            println!("cargo:rerun-if-changed={}", git_file);
+        }
+    }
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=Cargo.toml");
+    println!("cargo:rerun-if-changed=build.rs");
+}
@@ -0,0 +1,450 @@
+use std::{fs::File, path::Path};
+
+use rand::{distributions::WeightedIndex, rngs::StdRng, Rng, SeedableRng};
+use rand_distr::{Distribution, Normal};
+use serde_derive::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+
+use super::{get_event_times, ObservedReport};
+use crate::runner::{
+    design::{get_priors, Prior},
+    parameters::{get_replicate_seed, Experiment, Parameters},
+    sweep::set_parameter,
+};
+
+fn default_interval() -> f64 {
+    1.0
+}
+
+/// Counts of events in consecutive bins, the first starting at time 0
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Observations {
+    pub report: ObservedReport,
+    /// Width of each bin in days
+    #[serde(default = "default_interval")]
+    pub interval: f64,
+    #[serde(default)]
+    pub counts: Vec<f64>,
+    /// CSV file with a `count` column, read instead of `counts`
+    #[serde(default)]
+    pub file: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ObservedCount {
+    count: f64,
+}
+
+impl Observations {
+    pub fn get_counts(&self) -> Vec<f64> {
+        let Some(file) = &self.file else {
+            return self.counts.clone();
+        };
+        let file = File::open(file)
+            .unwrap_or_else(|_| panic!("Could not open observations file: {}", file));
+        csv::Reader::from_reader(file)
+            .deserialize::<ObservedCount>()
+            .map(|row| row.expect("Could not parse observations").count)
+            .collect()
+    }
+
+    /// Bins event times like the observations, ignoring events after the last bin
+    pub fn get_binned_counts(&self, times: impl Iterator<Item = f64>, bins: usize) -> Vec<f64> {
+        let mut counts = vec![0.0; bins];
+        for time in times {
+            let bin = (time / self.interval).floor() as usize;
+            if bin < bins {
+                counts[bin] += 1.0;
+            }
+        }
+        counts
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Distance {
+    #[default]
+    Euclidean,
+    Absolute,
+    /// Poisson deviance of the observations given the simulated counts as means
+    PoissonDeviance,
+}
+
+impl Distance {
+    pub fn get_distance(&self, observed: &[f64], simulated: &[f64]) -> f64 {
+        let pairs = observed.iter().zip(simulated);
+        match self {
+            Distance::Euclidean => pairs.map(|(o, s)| (o - s).powi(2)).sum::<f64>().sqrt(),
+            Distance::Absolute => pairs.map(|(o, s)| (o - s).abs()).sum(),
+            Distance::PoissonDeviance => pairs
+                .map(|(&o, &s)| {
+                    if o == 0.0 {
+                        return 2.0 * s;
+                    }
+                    // Keep the deviance finite when a bin has no simulated events
+                    let s = s.max(0.5);
+                    2.0 * (o * (o / s).ln() - (o - s))
+                })
+                .sum(),
+        }
+    }
+}
+
+fn default_quantile() -> f64 {
+    0.5
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum AbcMethod {
+    /// Keeps the `accepted` of `samples` prior draws that are closest to the observations
+    Rejection { samples: usize, accepted: usize },
+    /// Sequential Monte Carlo, with each generation's tolerance set to a `quantile` of the
+    /// previous generation's distances
+    Smc {
+        particles: usize,
+        generations: usize,
+        #[serde(default = "default_quantile")]
+        quantile: f64,
+        /// Simulations allowed per generation before giving up; defaults to 100 per particle
+        #[serde(default)]
+        max_simulations: Option<usize>,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CalibrationConfig {
+    pub base: Value,
+    /// Priors of the fitted fields by dotted path, as in a sampling design
+    pub parameters: Mapping,
+    pub observed: Observations,
+    #[serde(default)]
+    pub distance: Distance,
+    pub abc: AbcMethod,
+    /// Seeds sampling and, through derived seeds, every simulation
+    #[serde(default)]
+    pub seed: u64,
+}
+
+/// A parameter set with its importance weight and distance from the observations
+#[derive(Debug, Clone)]
+pub struct Particle {
+    pub values: Vec<f64>,
+    pub weight: f64,
+    pub distance: f64,
+}
+
+struct Calibration<'a> {
+    config: &'a CalibrationConfig,
+    priors: Vec<(String, Prior)>,
+    observed: Vec<f64>,
+    threads: u8,
+    rng: StdRng,
+}
+
+impl Calibration<'_> {
+    fn get_parameters(&self, values: &[f64], seed: u64) -> Parameters {
+        let mut parameters = self.config.base.clone();
+        for ((field, prior), value) in self.priors.iter().zip(values) {
+            set_parameter(&mut parameters, field, prior.get_value(*value));
+        }
+        let mut parameters: Parameters = serde_yaml::from_value(parameters)
+            .unwrap_or_else(|e| panic!("Invalid calibrated parameters: {}", e));
+        parameters.random_seed = seed;
+        parameters
+    }
+
+    fn sample_prior(&mut self) -> Vec<f64> {
+        let rng = &mut self.rng;
+        self.priors
+            .iter()
+            .map(|(_, prior)| prior.get_quantile(rng.gen()).as_f64().unwrap())
+            .collect()
+    }
+
+    // Runs candidates on the scenario runner and measures their distances. Simulations are
+    // numbered within each generation to derive their seeds.
+    async fn get_distances(
+        &self,
+        generation: usize,
+        first_simulation: usize,
+        candidates: &[Vec<f64>],
+    ) -> Vec<f64> {
+        let experiment = Experiment {
+            scenarios: candidates
+                .iter()
+                .enumerate()
+                .map(|(candidate, values)| {
+                    let simulation = first_simulation + candidate;
+                    let seed = get_replicate_seed(self.config.seed, generation, simulation);
+                    self.get_parameters(values, seed)
+                })
+                .collect(),
+            ..Default::default()
+        };
+        let scenarios = experiment.get_scenario_infos();
+        let times = get_event_times(self.config.observed.report, &scenarios, self.threads).await;
+        let observations = &self.config.observed;
+        times
+            .into_iter()
+            .map(|times| {
+                let simulated =
+                    observations.get_binned_counts(times.into_iter(), self.observed.len());
+                self.config
+                    .distance
+                    .get_distance(&self.observed, &simulated)
+            })
+            .collect()
+    }
+
+    async fn run_rejection(&mut self, samples: usize, accepted: usize) -> Vec<Particle> {
+        let candidates = (0..samples)
+            .map(|_| self.sample_prior())
+            .collect::<Vec<_>>();
+        let distances = self.get_distances(0, 0, &candidates).await;
+        let mut particles = candidates
+            .into_iter()
+            .zip(distances)
+            .map(|(values, distance)| Particle {
+                values,
+                weight: 0.0,
+                distance,
+            })
+            .collect::<Vec<_>>();
+        particles.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        particles.truncate(accepted);
+        let weight = 1.0 / particles.len() as f64;
+        for particle in particles.iter_mut() {
+            particle.weight = weight;
+        }
+        particles
+    }
+
+    // Draws from the previous generation and perturbs with its kernel until inside the prior
+    fn propose(
+        &mut self,
+        previous: &[Particle],
+        sampler: &WeightedIndex<f64>,
+        kernels: &[Normal<f64>],
+    ) -> Vec<f64> {
+        loop {
+            let particle = &previous[sampler.sample(&mut self.rng)];
+            let values = particle
+                .values
+                .iter()
+                .zip(kernels)
+                .zip(&self.priors)
+                .map(|((value, kernel), (_, prior))| {
+                    let value = value + kernel.sample(&mut self.rng);
+                    match prior {
+                        Prior::UniformInteger { .. } => value.round(),
+                        _ => value,
+                    }
+                })
+                .collect::<Vec<_>>();
+            if self.get_prior_density(&values) > 0.0 {
+                return values;
+            }
+        }
+    }
+
+    fn get_prior_density(&self, values: &[f64]) -> f64 {
+        self.priors
+            .iter()
+            .zip(values)
+            .map(|((_, prior), value)| prior.get_density(*value))
+            .product()
+    }
+
+    async fn run_smc(
+        &mut self,
+        particles: usize,
+        generations: usize,
+        quantile: f64,
+        max_simulations: usize,
+        mut write_generation: impl FnMut(usize, &[Particle]),
+    ) -> Result<Vec<Particle>, String> {
+        let mut population = self.run_rejection(particles, particles).await;
+        write_generation(0, &population);
+        for generation in 1..generations {
+            let mut distances = population.iter().map(|p| p.distance).collect::<Vec<_>>();
+            distances.sort_by(f64::total_cmp);
+            let tolerance = distances[((distances.len() - 1) as f64 * quantile).round() as usize];
+            let kernels = get_kernels(&population, self.priors.len());
+            let sampler = WeightedIndex::new(population.iter().map(|p| p.weight))
+                .expect("Particle weights should be positive");
+
+            // Propose in batches, so each batch runs in parallel
+            let mut accepted = Vec::new();
+            let mut simulations = 0;
+            while accepted.len() < particles {
+                if simulations >= max_simulations {
+                    return Err(format!(
+                        "Generation {} accepted {} of {} particles in {} simulations; tolerance {} \
+                         may be unreachable, so raise max_simulations or quantile",
+                        generation,
+                        accepted.len(),
+                        particles,
+                        simulations,
+                        tolerance
+                    ));
+                }
+                let batch = (particles - accepted.len()).min(max_simulations - simulations);
+                let candidates = (0..batch)
+                    .map(|_| self.propose(&population, &sampler, &kernels))
+                    .collect::<Vec<_>>();
+                let distances = self
+                    .get_distances(generation, simulations, &candidates)
+                    .await;
+                simulations += candidates.len();
+                accepted.extend(
+                    candidates
+                        .into_iter()
+                        .zip(distances)
+                        .filter(|(_, distance)| *distance <= tolerance),
+                );
+            }
+            eprintln!(
+                "Generation {}: tolerance {}, accepted {} of {} simulations",
+                generation, tolerance, particles, simulations
+            );
+
+            let weights = accepted
+                .iter()
+                .map(|(values, _)| {
+                    let kernel_density = population
+                        .iter()
+                        .map(|p| p.weight * get_kernel_density(&kernels, &p.values, values))
+                        .sum::<f64>();
+                    self.get_prior_density(values) / kernel_density
+                })
+                .collect::<Vec<_>>();
+            let total_weight = weights.iter().sum::<f64>();
+            population = accepted
+                .into_iter()
+                .zip(weights)
+                .map(|((values, distance), weight)| Particle {
+                    values,
+                    weight: weight / total_weight,
+                    distance,
+                })
+                .collect();
+            write_generation(generation, &population);
+        }
+        Ok(population)
+    }
+}
+
+// Gaussian perturbation kernels with twice the weighted variance of each parameter (Beaumont
+// et al. 2009)
+fn get_kernels(population: &[Particle], dimensions: usize) -> Vec<Normal<f64>> {
+    (0..dimensions)
+        .map(|dimension| {
+            let mean = population
+                .iter()
+                .map(|p| p.weight * p.values[dimension])
+                .sum::<f64>();
+            let variance = population
+                .iter()
+                .map(|p| p.weight * (p.values[dimension] - mean).powi(2))
+                .sum::<f64>();
+            Normal::new(0.0, (2.0 * variance).sqrt()).expect("Kernel variance should be finite")
+        })
+        .collect()
+}
+
+// Up to a constant factor, which cancels when weights are normalized
+fn get_kernel_density(kernels: &[Normal<f64>], from: &[f64], to: &[f64]) -> f64 {
+    kernels
+        .iter()
+        .zip(from.iter().zip(to))
+        .filter(|(kernel, _)| kernel.std_dev() > 0.0)
+        .map(|(kernel, (from, to))| {
+            let z = (to - from) / kernel.std_dev();
+            (-z * z / 2.0).exp() / kernel.std_dev()
+        })
+        .product()
+}
+
+/// Fits the config's priors to its observations with ABC, writing the particles of every
+/// generation to `posterior.csv`. The last generation is the posterior sample. Fails if an
+/// SMC generation runs out of simulations, keeping the generations written so far.
+pub async fn run_calibration(
+    config: CalibrationConfig,
+    output_path: &Path,
+    threads: u8,
+) -> Result<(), String> {
+    let priors = get_priors(&config.parameters);
+    let observed = config.observed.get_counts();
+    let mut writer = csv::Writer::from_path(output_path.join("posterior.csv"))
+        .expect("Could not create posterior.csv");
+    let header = ["generation"]
+        .into_iter()
+        .chain(priors.iter().map(|(field, _)| field.as_str()))
+        .chain(["weight", "distance"]);
+    writer
+        .write_record(header)
+        .expect("Could not write posterior");
+    let mut write_generation = |generation: usize, particles: &[Particle]| {
+        for particle in particles {
+            let record = [generation.to_string()]
+                .into_iter()
+                .chain(particle.values.iter().map(f64::to_string))
+                .chain([particle.weight.to_string(), particle.distance.to_string()]);
+            writer
+                .write_record(record)
+                .expect("Could not write posterior");
+        }
+    };
+
+    let mut calibration = Calibration {
+        config: &config,
+        priors,
+        observed,
+        threads,
+        rng: StdRng::seed_from_u64(config.seed),
+    };
+    let result = match config.abc {
+        AbcMethod::Rejection { samples, accepted } => {
+            let particles = calibration.run_rejection(samples, accepted).await;
+            write_generation(0, &particles);
+            Ok(())
+        }
+        AbcMethod::Smc {
+            particles,
+            generations,
+            quantile,
+            max_simulations,
+        } => calibration
+            .run_smc(
+                particles,
+                generations,
+                quantile,
+                max_simulations.unwrap_or(100 * particles),
+                &mut write_generation,
+            )
+            .await
+            .map(|_| ()),
+    };
+    writer.flush().expect("Could not write posterior");
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distances_are_zero_for_matching_counts() {
+        let counts = [0.0, 3.0, 7.0];
+        for distance in [
+            Distance::Euclidean,
+            Distance::Absolute,
+            Distance::PoissonDeviance,
+        ] {
+            assert_eq!(distance.get_distance(&counts, &[0.0, 3.0, 7.0]), 0.0);
+            assert!(distance.get_distance(&counts, &[1.0, 3.0, 5.0]) > 0.0);
+        }
+    }
+}
@@ -0,0 +1,98 @@
+pub mod calibration;
+pub mod particle_filter;
+pub mod sensitivity;
+
+use std::path::Path;
+
+use eosim::reports::Report;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::output::{MemorySink, OutputFormat, ReportOutput};
+use crate::runner::{
+    parameters::{OutputConfig, Scenario, ScenarioInfo},
+    reports::{add_report_sinks, ReportSinks},
+    run_multi_threaded, run_single_threaded,
+};
+use crate::sir::{
+    death_report::DeathReport, incidence_report::IncidenceReport,
+    observed_case_report::ObservedCaseReport, summary_report::RunSummary,
+};
+
+/// Runs scenarios with every report discarded except `R`, and returns its rows by scenario
+pub async fn collect_report<R>(
+    scenarios: &[ScenarioInfo],
+    name: &str,
+    threads: u8,
+) -> Vec<Vec<R::Item>>
+where
+    R: Report + 'static,
+    R::Item: Serialize + DeserializeOwned + Send + 'static,
+{
+    let rows = MemorySink::<Scenario, R::Item>::new();
+    let mut sinks = ReportSinks::new(scenarios);
+    add_report_sinks(
+        &mut sinks,
+        &ReportOutput::new(Path::new(".")),
+        &OutputConfig::default(),
+        OutputFormat::Null,
+    );
+    sinks.add::<R, Scenario>(name, Box::new(rows.clone()), ScenarioInfo::get_key);
+    if threads <= 1 {
+        run_single_threaded(scenarios, sinks);
+    } else {
+        run_multi_threaded(scenarios, sinks, threads, false).await;
+    }
+
+    let mut items = (0..scenarios.len()).map(|_| Vec::new()).collect::<Vec<_>>();
+    for (key, item) in rows.rows().drain(..) {
+        items[key.scenario].push(item);
+    }
+    items
+}
+
+/// Reads a numeric field of the summary report, such as `attack_rate`
+pub fn get_summary_output(summary: &RunSummary, output: &str) -> f64 {
+    serde_json::to_value(summary)
+        .expect("Could not serialize summary")
+        .get(output)
+        .and_then(serde_json::Value::as_f64)
+        .unwrap_or_else(|| panic!("Unknown summary output {}", output))
+}
+
+/// Report whose events are counted to compare with observations
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ObservedReport {
+    Incidence,
+    Deaths,
+    ObservedCases,
+}
+
+/// Runs scenarios and returns the times of the events in `report` by scenario
+pub async fn get_event_times(
+    report: ObservedReport,
+    scenarios: &[ScenarioInfo],
+    threads: u8,
+) -> Vec<Vec<f64>> {
+    match report {
+        ObservedReport::Incidence => {
+            collect_report::<IncidenceReport>(scenarios, "incidence_report", threads)
+                .await
+                .into_iter()
+                .map(|items| items.iter().map(|item| item.time).collect())
+                .collect()
+        }
+        ObservedReport::Deaths => collect_report::<DeathReport>(scenarios, "death_report", threads)
+            .await
+            .into_iter()
+            .map(|items| items.iter().map(|item| item.time).collect())
+            .collect(),
+        ObservedReport::ObservedCases => {
+            collect_report::<ObservedCaseReport>(scenarios, "observed_case_report", threads)
+                .await
+                .into_iter()
+                .map(|items| items.iter().map(|item| item.time).collect())
+                .collect()
+        }
+    }
+}
@@ -0,0 +1,272 @@
+use std::path::Path;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde_derive::{Deserialize, Serialize};
+
+use super::{calibration::Observations, get_event_times};
+use crate::runner::parameters::{get_replicate_seed, Experiment, Parameters, Reseed};
+
+/// Fixed parameters, the observations to assimilate and the forecast to make
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FilterConfig {
+    pub parameters: Parameters,
+    pub observed: Observations,
+    pub particles: usize,
+    /// Number of bins of `observed.interval` days to forecast past the last observation
+    #[serde(default)]
+    pub forecast_bins: usize,
+    /// Seeds resampling and, through derived seeds, every particle
+    #[serde(default)]
+    pub seed: u64,
+}
+
+// Poisson log likelihood of an observed count, with the simulated count as the mean. The mean
+// is at least 0.5 so that a bin without simulated events doesn't rule a particle out.
+fn get_log_likelihood(observed: f64, simulated: f64) -> f64 {
+    let mean = simulated.max(0.5);
+    let log_factorial = (1..=observed.round() as u64)
+        .map(|k| (k as f64).ln())
+        .sum::<f64>();
+    observed * mean.ln() - mean - log_factorial
+}
+
+/// Systematic resampling: the indices of the particles that each new particle descends from
+pub fn get_ancestors(weights: &[f64], rng: &mut StdRng) -> Vec<usize> {
+    let n = weights.len();
+    let start = rng.gen::<f64>() / n as f64;
+    let mut ancestors = Vec::with_capacity(n);
+    let mut ancestor = 0;
+    let mut cumulative = weights[0];
+    for i in 0..n {
+        let u = start + i as f64 / n as f64;
+        while u > cumulative && ancestor < n - 1 {
+            ancestor += 1;
+            cumulative += weights[ancestor];
+        }
+        ancestors.push(ancestor);
+    }
+    ancestors
+}
+
+// Value below which a share `q` of the sorted values fall
+fn get_quantile(sorted: &[f64], q: f64) -> f64 {
+    sorted[((sorted.len() - 1) as f64 * q).round() as usize]
+}
+
+/// One row of `filter.csv`, for the bin ending at `time`
+#[derive(Debug, Serialize)]
+pub struct FilterStep {
+    pub time: f64,
+    pub observed: f64,
+    /// Mean and 95% interval of the particles' counts before weighting
+    pub predicted_mean: f64,
+    pub predicted_low: f64,
+    pub predicted_high: f64,
+    /// Mean of the particles' counts weighted by their likelihood
+    pub filtered_mean: f64,
+    pub effective_sample_size: f64,
+    /// Estimated log likelihood of the observations so far
+    pub log_likelihood: f64,
+}
+
+/// One row of `forecast.csv`, for the bin ending at `time`
+#[derive(Debug, Serialize)]
+pub struct ForecastBin {
+    pub time: f64,
+    pub mean: f64,
+    pub low: f64,
+    pub median: f64,
+    pub high: f64,
+}
+
+struct ParticleFilter<'a> {
+    config: &'a FilterConfig,
+    threads: u8,
+}
+
+impl ParticleFilter<'_> {
+    // Replays each particle from its seeds, one per segment between observations, and bins
+    // its events
+    async fn get_counts(&self, histories: &[Vec<u64>], bins: usize) -> Vec<Vec<f64>> {
+        let observations = &self.config.observed;
+        let experiment = Experiment {
+            scenarios: histories
+                .iter()
+                .map(|seeds| {
+                    let mut parameters = self.config.parameters.clone();
+                    parameters.random_seed = seeds[0];
+                    parameters.reseed = seeds
+                        .iter()
+                        .enumerate()
+                        .skip(1)
+                        .map(|(segment, &random_seed)| Reseed {
+                            time: segment as f64 * observations.interval,
+                            random_seed,
+                        })
+                        .collect();
+                    parameters
+                })
+                .collect(),
+            ..Default::default()
+        };
+        let scenarios = experiment.get_scenario_infos();
+        get_event_times(observations.report, &scenarios, self.threads)
+            .await
+            .into_iter()
+            .map(|times| observations.get_binned_counts(times.into_iter(), bins))
+            .collect()
+    }
+}
+
+/// Assimilates the observations with a bootstrap particle filter, writing each step to
+/// `filter.csv` and the forecast to `forecast.csv`. Simulation states can't be copied, so
+/// each particle is a history of seeds and is replayed from the start at every step;
+/// particles that share an ancestor get new seeds for the next segment, so they diverge.
+pub async fn run_particle_filter(config: FilterConfig, output_path: &Path, threads: u8) {
+    let observed = config.observed.get_counts();
+    let interval = config.observed.interval;
+    let particles = config.particles;
+    let filter = ParticleFilter {
+        config: &config,
+        threads,
+    };
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let mut histories = (0..particles)
+        .map(|particle| vec![get_replicate_seed(config.seed, 0, particle)])
+        .collect::<Vec<_>>();
+
+    let mut writer = csv::Writer::from_path(output_path.join("filter.csv"))
+        .expect("Could not create filter.csv");
+    let mut log_likelihood = 0.0;
+    for (step, &observed_count) in observed.iter().enumerate() {
+        let simulated = filter
+            .get_counts(&histories, step + 1)
+            .await
+            .into_iter()
+            .map(|counts| counts[step])
+            .collect::<Vec<_>>();
+
+        // Weight by likelihood, scaled by the largest to avoid underflow
+        let log_weights = simulated
+            .iter()
+            .map(|&count| get_log_likelihood(observed_count, count))
+            .collect::<Vec<_>>();
+        let max_log_weight = log_weights.iter().copied().fold(f64::MIN, f64::max);
+        let weights = log_weights
+            .iter()
+            .map(|log_weight| (log_weight - max_log_weight).exp())
+            .collect::<Vec<_>>();
+        let total_weight = weights.iter().sum::<f64>();
+        log_likelihood += max_log_weight + (total_weight / particles as f64).ln();
+        let weights = weights
+            .iter()
+            .map(|weight| weight / total_weight)
+            .collect::<Vec<_>>();
+
+        let mut sorted = simulated.clone();
+        sorted.sort_by(f64::total_cmp);
+        let filter_step = FilterStep {
+            time: (step + 1) as f64 * interval,
+            observed: observed_count,
+            predicted_mean: sorted.iter().sum::<f64>() / particles as f64,
+            predicted_low: get_quantile(&sorted, 0.025),
+            predicted_high: get_quantile(&sorted, 0.975),
+            filtered_mean: weights.iter().zip(&simulated).map(|(w, s)| w * s).sum(),
+            effective_sample_size: 1.0 / weights.iter().map(|w| w * w).sum::<f64>(),
+            log_likelihood,
+        };
+        eprintln!(
+            "Step {}: effective sample size {:.1}",
+            step, filter_step.effective_sample_size
+        );
+        writer
+            .serialize(filter_step)
+            .expect("Could not write filter step");
+
+        histories = get_ancestors(&weights, &mut rng)
+            .into_iter()
+            .enumerate()
+            .map(|(particle, ancestor)| {
+                let mut seeds = histories[ancestor].clone();
+                seeds.push(get_replicate_seed(config.seed, step + 1, particle));
+                seeds
+            })
+            .collect();
+    }
+    writer.flush().expect("Could not write filter steps");
+
+    // Run the resampled particles on past the last observation
+    let bins = observed.len() + config.forecast_bins;
+    let counts = filter.get_counts(&histories, bins).await;
+    let mut writer = csv::Writer::from_path(output_path.join("forecast.csv"))
+        .expect("Could not create forecast.csv");
+    for bin in observed.len()..bins {
+        let mut sorted = counts.iter().map(|counts| counts[bin]).collect::<Vec<_>>();
+        sorted.sort_by(f64::total_cmp);
+        let forecast_bin = ForecastBin {
+            time: (bin + 1) as f64 * interval,
+            mean: sorted.iter().sum::<f64>() / particles as f64,
+            low: get_quantile(&sorted, 0.025),
+            median: get_quantile(&sorted, 0.5),
+            high: get_quantile(&sorted, 0.975),
+        };
+        writer
+            .serialize(forecast_bin)
+            .expect("Could not write forecast");
+    }
+    writer.flush().expect("Could not write forecast");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::ObservedReport;
+
+    #[tokio::test]
+    async fn test_reseeding_diverges_runs_from_the_reseed_time() {
+        let scenarios = Experiment {
+            scenarios: [2, 3]
+                .into_iter()
+                .map(|reseed_seed| {
+                    serde_yaml::from_str::<Parameters>(&format!(
+                        "population: 10000
+r0: 2.0
+infectious_period: 4.0
+initial_infections: 10
+random_seed: 1
+death_rate: 0.0
+reseed:
+  - time: 10.0
+    random_seed: {}",
+                        reseed_seed
+                    ))
+                    .unwrap()
+                })
+                .collect(),
+            ..Default::default()
+        }
+        .get_scenario_infos();
+        let times = get_event_times(ObservedReport::Incidence, &scenarios, 1).await;
+        let split = |times: &[f64]| {
+            let (before, after): (Vec<f64>, Vec<f64>) =
+                times.iter().partition(|time| **time < 10.0);
+            (before, after)
+        };
+        let (first_before, first_after) = split(&times[0]);
+        let (second_before, second_after) = split(&times[1]);
+        assert!(!first_before.is_empty());
+        assert_eq!(first_before, second_before);
+        assert!(!first_after.is_empty());
+        assert_ne!(first_after, second_after);
+    }
+
+    #[test]
+    fn test_systematic_resampling_follows_weights() {
+        let mut rng = StdRng::seed_from_u64(1);
+        assert_eq!(get_ancestors(&[0.0, 1.0, 0.0], &mut rng), vec![1, 1, 1]);
+        assert_eq!(
+            get_ancestors(&[0.25, 0.25, 0.0, 0.5], &mut rng),
+            vec![0, 1, 3, 3]
+        );
+    }
+}
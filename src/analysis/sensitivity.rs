@@ -0,0 +1,246 @@
+use std::path::Path;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde_derive::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+
+use super::{collect_report, get_summary_output};
+use crate::runner::{
+    design::{get_priors, get_sampled_parameters, write_design_matrix, SobolSequence},
+    parameters::Experiment,
+};
+use crate::sir::summary_report::SummaryReport;
+
+fn default_bootstrap_samples() -> usize {
+    1000
+}
+
+fn default_confidence_level() -> f64 {
+    0.95
+}
+
+/// A Saltelli design over priors of selected fields and the summary outputs to analyze
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SensitivityConfig {
+    pub base: Value,
+    /// Priors of the varied fields by dotted path, as in a sampling design
+    pub parameters: Mapping,
+    /// Number of base samples; `samples * (parameters + 2)` scenarios are run
+    pub samples: usize,
+    /// Fields of the summary report, such as `attack_rate`
+    pub outputs: Vec<String>,
+    #[serde(default = "default_bootstrap_samples")]
+    pub bootstrap_samples: usize,
+    #[serde(default = "default_confidence_level")]
+    pub confidence_level: f64,
+    /// Seeds the bootstrap
+    #[serde(default)]
+    pub seed: u64,
+}
+
+/// Points of the unit cube in Saltelli's scheme: for each sample, rows A and B from a Sobol
+/// sequence of twice the dimensions, then A with each column in turn taken from B
+pub fn get_saltelli_points(samples: usize, dimensions: usize) -> Vec<Vec<f64>> {
+    let mut sequence = SobolSequence::new(2 * dimensions);
+    let mut points = Vec::with_capacity(samples * (dimensions + 2));
+    for _ in 0..samples {
+        let point = sequence.next_point();
+        let (a, b) = point.split_at(dimensions);
+        points.push(a.to_vec());
+        points.push(b.to_vec());
+        for (dimension, &value) in b.iter().enumerate() {
+            let mut ab = a.to_vec();
+            ab[dimension] = value;
+            points.push(ab);
+        }
+    }
+    points
+}
+
+/// First-order (Saltelli 2010) and total-order (Jansen) indices of each dimension, estimated
+/// from the outputs of the given samples of a Saltelli design
+pub fn get_sobol_indices(
+    outputs: &[f64],
+    dimensions: usize,
+    samples: &[usize],
+) -> (Vec<f64>, Vec<f64>) {
+    let get_output = |sample: usize, row: usize| outputs[sample * (dimensions + 2) + row];
+    let n = samples.len() as f64;
+    let mean = samples
+        .iter()
+        .map(|&sample| get_output(sample, 0) + get_output(sample, 1))
+        .sum::<f64>()
+        / (2.0 * n);
+    let variance = samples
+        .iter()
+        .map(|&sample| {
+            (get_output(sample, 0) - mean).powi(2) + (get_output(sample, 1) - mean).powi(2)
+        })
+        .sum::<f64>()
+        / (2.0 * n);
+
+    let mut first_order = Vec::with_capacity(dimensions);
+    let mut total_order = Vec::with_capacity(dimensions);
+    for dimension in 0..dimensions {
+        let mut first = 0.0;
+        let mut total = 0.0;
+        for &sample in samples {
+            let a = get_output(sample, 0);
+            let b = get_output(sample, 1);
+            let ab = get_output(sample, dimension + 2);
+            first += b * (ab - a);
+            total += (a - ab).powi(2) / 2.0;
+        }
+        first_order.push(first / n / variance);
+        total_order.push(total / n / variance);
+    }
+    (first_order, total_order)
+}
+
+// Percentile interval of bootstrap estimates
+fn get_interval(mut estimates: Vec<f64>, confidence_level: f64) -> (f64, f64) {
+    if estimates.is_empty() {
+        return (f64::NAN, f64::NAN);
+    }
+    estimates.sort_by(f64::total_cmp);
+    let get_quantile = |q: f64| estimates[((estimates.len() - 1) as f64 * q).round() as usize];
+    let tail = (1.0 - confidence_level) / 2.0;
+    (get_quantile(tail), get_quantile(1.0 - tail))
+}
+
+/// One row of `sensitivity.csv`
+#[derive(Debug, Serialize)]
+pub struct SensitivityIndex {
+    pub output: String,
+    pub parameter: String,
+    pub first_order: f64,
+    pub first_order_low: f64,
+    pub first_order_high: f64,
+    pub total_order: f64,
+    pub total_order_low: f64,
+    pub total_order_high: f64,
+}
+
+/// Estimates indices with bootstrap intervals for one output
+pub fn get_sensitivity_indices(
+    config: &SensitivityConfig,
+    parameters: &[String],
+    output: &str,
+    outputs: &[f64],
+    rng: &mut StdRng,
+) -> Vec<SensitivityIndex> {
+    let dimensions = parameters.len();
+    let all_samples = (0..config.samples).collect::<Vec<_>>();
+    let (first_order, total_order) = get_sobol_indices(outputs, dimensions, &all_samples);
+
+    let mut first_estimates = vec![Vec::new(); dimensions];
+    let mut total_estimates = vec![Vec::new(); dimensions];
+    for _ in 0..config.bootstrap_samples {
+        let samples = (0..config.samples)
+            .map(|_| rng.gen_range(0..config.samples))
+            .collect::<Vec<_>>();
+        let (first, total) = get_sobol_indices(outputs, dimensions, &samples);
+        for (estimates, estimate) in first_estimates.iter_mut().zip(first) {
+            estimates.push(estimate);
+        }
+        for (estimates, estimate) in total_estimates.iter_mut().zip(total) {
+            estimates.push(estimate);
+        }
+    }
+
+    parameters
+        .iter()
+        .zip(first_estimates.into_iter().zip(total_estimates))
+        .enumerate()
+        .map(|(dimension, (parameter, (first, total)))| {
+            let (first_order_low, first_order_high) = get_interval(first, config.confidence_level);
+            let (total_order_low, total_order_high) = get_interval(total, config.confidence_level);
+            SensitivityIndex {
+                output: output.to_string(),
+                parameter: parameter.clone(),
+                first_order: first_order[dimension],
+                first_order_low,
+                first_order_high,
+                total_order: total_order[dimension],
+                total_order_low,
+                total_order_high,
+            }
+        })
+        .collect()
+}
+
+/// Runs the Saltelli design and writes `sensitivity.csv`, along with the design matrix in
+/// `design.csv`. Every scenario uses the base `random_seed`.
+pub async fn run_sensitivity_analysis(config: SensitivityConfig, output_path: &Path, threads: u8) {
+    let priors = get_priors(&config.parameters);
+    let experiment = Experiment {
+        parameter_columns: priors.iter().map(|(field, _)| field.clone()).collect(),
+        scenarios: get_saltelli_points(config.samples, priors.len())
+            .iter()
+            .map(|point| get_sampled_parameters(&config.base, &priors, point))
+            .collect(),
+        ..Default::default()
+    };
+    write_design_matrix(output_path, &experiment);
+    eprintln!(
+        "Running {} scenarios for {} parameters",
+        experiment.scenarios.len(),
+        priors.len()
+    );
+
+    let scenarios = experiment.get_scenario_infos();
+    let summaries = collect_report::<SummaryReport>(&scenarios, "summary", threads)
+        .await
+        .into_iter()
+        .map(|mut summaries| {
+            summaries
+                .pop()
+                .expect("Every scenario should have a summary")
+        })
+        .collect::<Vec<_>>();
+
+    let mut writer = csv::Writer::from_path(output_path.join("sensitivity.csv"))
+        .expect("Could not create sensitivity.csv");
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    for output in &config.outputs {
+        let outputs = summaries
+            .iter()
+            .map(|summary| get_summary_output(summary, output))
+            .collect::<Vec<_>>();
+        let indices = get_sensitivity_indices(
+            &config,
+            &experiment.parameter_columns,
+            output,
+            &outputs,
+            &mut rng,
+        );
+        for index in indices {
+            writer
+                .serialize(index)
+                .expect("Could not write sensitivity indices");
+        }
+    }
+    writer.flush().expect("Could not write sensitivity indices");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sobol_indices_of_additive_function() {
+        // f = 4 x0 + x1 - 1 is additive with variances 16/12 and 1/12, and ignores x2
+        let samples = 1024;
+        let outputs = get_saltelli_points(samples, 3)
+            .iter()
+            .map(|point| 4.0 * point[0] + point[1] - 1.0)
+            .collect::<Vec<_>>();
+        let (first_order, total_order) =
+            get_sobol_indices(&outputs, 3, &(0..samples).collect::<Vec<_>>());
+        let expected = [16.0 / 17.0, 1.0 / 17.0, 0.0];
+        for ((first, total), expected) in first_order.iter().zip(&total_order).zip(expected) {
+            assert!((first - expected).abs() < 0.02);
+            assert!((total - expected).abs() < 0.02);
+        }
+    }
+}
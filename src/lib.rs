@@ -1 +1,4 @@
+pub mod analysis;
+pub mod output;
+pub mod runner;
 pub mod sir;
@@ -0,0 +1,70 @@
+use std::{
+    cell::RefCell,
+    io::{LineWriter, Write},
+    rc::Rc,
+};
+
+use serde::Serialize;
+
+use super::{get_row_object, OutputResult, ReportSink};
+
+/// Writes each row as a single flat JSON object on its own line
+pub struct JsonLinesWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> JsonLinesWriter<W> {
+    pub fn new(writer: W) -> Self {
+        JsonLinesWriter { writer }
+    }
+}
+
+impl<K: Serialize, T: Serialize, W: Write> ReportSink<K, T> for JsonLinesWriter<W> {
+    fn write(&mut self, key: K, item: T) -> OutputResult<()> {
+        serde_json::to_writer(&mut self.writer, &get_row_object(key, item)?)?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> OutputResult<()> {
+        Ok(self.writer.flush()?)
+    }
+}
+
+pub type SharedStream = Rc<RefCell<LineWriter<Box<dyn Write>>>>;
+
+/// Writes the rows of one report to a stream shared by several reports, tagging each row
+/// with the report's name. Lines are flushed as they are written so that consumers see
+/// results while the simulation is still running.
+pub struct StreamWriter {
+    report: String,
+    stream: SharedStream,
+}
+
+impl StreamWriter {
+    pub fn new(report: &str, stream: SharedStream) -> Self {
+        StreamWriter {
+            report: report.to_string(),
+            stream,
+        }
+    }
+}
+
+impl<K: Serialize, T: Serialize> ReportSink<K, T> for StreamWriter {
+    fn write(&mut self, key: K, item: T) -> OutputResult<()> {
+        let mut row = serde_json::Map::new();
+        row.insert(
+            "report".to_string(),
+            serde_json::Value::String(self.report.clone()),
+        );
+        row.extend(get_row_object(key, item)?);
+        let mut stream = self.stream.borrow_mut();
+        serde_json::to_writer(&mut *stream, &row)?;
+        stream.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> OutputResult<()> {
+        Ok(self.stream.borrow_mut().flush()?)
+    }
+}
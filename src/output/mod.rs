@@ -0,0 +1,299 @@
+pub mod json_lines;
+pub mod parquet;
+pub mod per_scenario;
+pub mod sqlite;
+
+use std::{
+    cell::{OnceCell, RefCell},
+    error::Error,
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, LineWriter, Write},
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use clap::ValueEnum;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use self::{
+    json_lines::{JsonLinesWriter, SharedStream, StreamWriter},
+    parquet::ParquetWriter,
+    per_scenario::PerScenarioSink,
+    sqlite::{SqliteDatabase, SqliteTable},
+};
+
+pub type OutputResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
+
+/// Receives the rows of one report, each made of a key identifying the scenario and a
+/// report item
+pub trait ReportSink<K, T> {
+    fn write(&mut self, key: K, item: T) -> OutputResult<()>;
+
+    /// Called once a scenario has released all of its rows
+    fn finish_scenario(&mut self, _scenario: usize) -> OutputResult<()> {
+        Ok(())
+    }
+
+    /// Flushes any buffered rows; called once all rows have been written
+    fn finish(&mut self) -> OutputResult<()> {
+        Ok(())
+    }
+}
+
+/// A report key that identifies the scenario a row belongs to
+pub trait ScenarioKey {
+    fn scenario(&self) -> usize;
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    Csv,
+    /// One JSON object per line
+    Jsonl,
+    Parquet,
+    /// A single results.sqlite database with one table per report
+    Sqlite,
+    /// Tagged JSON lines on the output stream, stdout unless another target is given
+    Stream,
+    /// Discard the report
+    #[serde(rename = "none")]
+    #[value(name = "none")]
+    Null,
+}
+
+impl OutputFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Csv => "csv",
+            OutputFormat::Jsonl => "jsonl",
+            OutputFormat::Parquet => "parquet",
+            OutputFormat::Sqlite => "sqlite",
+            OutputFormat::Stream | OutputFormat::Null => "",
+        }
+    }
+}
+
+/// How report files are arranged in the output directory
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputLayout {
+    /// One file per report holding every scenario
+    Combined,
+    /// A directory per scenario holding one file per report and the scenario's parameters
+    PerScenario,
+}
+
+/// Merges the fields of a key and an item into a single JSON object
+pub fn get_row_object<K: Serialize, T: Serialize>(
+    key: K,
+    item: T,
+) -> OutputResult<serde_json::Map<String, serde_json::Value>> {
+    let mut row = match serde_json::to_value(key)? {
+        serde_json::Value::Object(row) => row,
+        _ => return Err("Report keys must serialize to structs".into()),
+    };
+    match serde_json::to_value(item)? {
+        serde_json::Value::Object(item) => row.extend(item),
+        item => {
+            row.insert("value".to_string(), item);
+        }
+    }
+    Ok(row)
+}
+
+impl<K: Serialize, T: Serialize> ReportSink<K, T> for csv::Writer<File> {
+    fn write(&mut self, key: K, item: T) -> OutputResult<()> {
+        Ok(self.serialize((key, item))?)
+    }
+
+    fn finish(&mut self) -> OutputResult<()> {
+        Ok(self.flush()?)
+    }
+}
+
+/// Collects rows in memory, for tests and for callers that analyse results directly
+pub struct MemorySink<K, T> {
+    rows: Arc<Mutex<Vec<(K, T)>>>,
+}
+
+impl<K, T> MemorySink<K, T> {
+    pub fn new() -> Self {
+        MemorySink {
+            rows: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    pub fn rows(&self) -> MutexGuard<'_, Vec<(K, T)>> {
+        self.rows.lock().expect("Memory sink lock poisoned")
+    }
+}
+
+impl<K, T> Default for MemorySink<K, T> {
+    fn default() -> Self {
+        MemorySink::new()
+    }
+}
+
+// Clones share the same rows, so a clone can be kept to read what was written
+impl<K, T> Clone for MemorySink<K, T> {
+    fn clone(&self) -> Self {
+        MemorySink {
+            rows: self.rows.clone(),
+        }
+    }
+}
+
+impl<K, T> ReportSink<K, T> for MemorySink<K, T> {
+    fn write(&mut self, key: K, item: T) -> OutputResult<()> {
+        self.rows().push((key, item));
+        Ok(())
+    }
+}
+
+pub struct NullSink;
+
+impl<K, T> ReportSink<K, T> for NullSink {
+    fn write(&mut self, _: K, _: T) -> OutputResult<()> {
+        Ok(())
+    }
+}
+
+/// Creates a sink writing to the file for the report called `name` in `path`
+pub fn create_file_sink<K, T>(
+    path: &Path,
+    name: &str,
+    format: OutputFormat,
+    keys: &[K],
+) -> Box<dyn ReportSink<K, T>>
+where
+    K: Serialize + 'static,
+    T: Serialize + DeserializeOwned + 'static,
+{
+    let file = File::create(path.join(format!("{}.{}", name, format.extension())))
+        .unwrap_or_else(|_| panic!("Could not create {} file.", name));
+    match format {
+        OutputFormat::Csv => Box::new(csv::Writer::from_writer(file)),
+        OutputFormat::Jsonl => Box::new(JsonLinesWriter::new(BufWriter::new(file))),
+        OutputFormat::Parquet => Box::new(
+            ParquetWriter::new(file, keys)
+                .unwrap_or_else(|e| panic!("Could not create {} writer: {}", name, e)),
+        ),
+        _ => panic!("{:?} is not a file format", format),
+    }
+}
+
+/// The directory that report files and the results database are written to, and the
+/// target of streamed reports
+pub struct ReportOutput {
+    path: PathBuf,
+    database: OnceCell<Rc<SqliteDatabase>>,
+    layout: OutputLayout,
+    stream_target: Option<PathBuf>,
+    stream: OnceCell<SharedStream>,
+}
+
+impl ReportOutput {
+    pub fn new(path: &Path) -> Self {
+        ReportOutput {
+            path: path.to_path_buf(),
+            database: OnceCell::new(),
+            layout: OutputLayout::Combined,
+            stream_target: None,
+            stream: OnceCell::new(),
+        }
+    }
+
+    /// Sets the layout of report files; the results database and the stream always hold
+    /// every scenario
+    pub fn set_layout(&mut self, layout: OutputLayout) {
+        self.layout = layout;
+    }
+
+    pub fn layout(&self) -> OutputLayout {
+        self.layout
+    }
+
+    /// Streams reports to the file or named pipe at `target` instead of stdout; `-` is
+    /// stdout
+    pub fn set_stream_target(&mut self, target: &str) {
+        self.stream_target = match target {
+            "-" => None,
+            _ => Some(PathBuf::from(target)),
+        };
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether any report has been written to the results database
+    pub fn has_database(&self) -> bool {
+        self.database.get().is_some()
+    }
+
+    fn get_database(&self) -> Rc<SqliteDatabase> {
+        self.database
+            .get_or_init(|| {
+                Rc::new(
+                    SqliteDatabase::create(&self.path.join("results.sqlite"))
+                        .unwrap_or_else(|e| panic!("Could not create results database: {}", e)),
+                )
+            })
+            .clone()
+    }
+
+    fn get_stream(&self) -> SharedStream {
+        self.stream
+            .get_or_init(|| {
+                let stream: Box<dyn Write> = match &self.stream_target {
+                    None => Box::new(io::stdout()),
+                    // Opening without truncating also works for a named pipe
+                    Some(target) => Box::new(
+                        OpenOptions::new()
+                            .write(true)
+                            .create(true)
+                            .truncate(false)
+                            .open(target)
+                            .unwrap_or_else(|_| {
+                                panic!("Could not open stream target {}", target.display())
+                            }),
+                    ),
+                };
+                Rc::new(RefCell::new(LineWriter::new(stream)))
+            })
+            .clone()
+    }
+
+    /// Creates the sink for the report called `name`: a file named after the report (or one
+    /// per scenario), a table in the results database, or the stream
+    pub fn create_sink<K, T>(
+        &self,
+        name: &str,
+        format: OutputFormat,
+        keys: &[K],
+    ) -> Box<dyn ReportSink<K, T>>
+    where
+        K: Serialize + ScenarioKey + Clone + 'static,
+        T: Serialize + DeserializeOwned + 'static,
+    {
+        match format {
+            OutputFormat::Csv | OutputFormat::Jsonl | OutputFormat::Parquet => match self.layout {
+                OutputLayout::Combined => create_file_sink(&self.path, name, format, keys),
+                OutputLayout::PerScenario => Box::new(PerScenarioSink::new(
+                    &self.path,
+                    name,
+                    format,
+                    keys.to_vec(),
+                )),
+            },
+            OutputFormat::Sqlite => Box::new(
+                SqliteTable::new(self.get_database(), name, keys)
+                    .unwrap_or_else(|e| panic!("Could not create {} table: {}", name, e)),
+            ),
+            OutputFormat::Stream => Box::new(StreamWriter::new(name, self.get_stream())),
+            OutputFormat::Null => Box::new(NullSink),
+        }
+    }
+}
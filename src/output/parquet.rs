@@ -0,0 +1,108 @@
+use std::{fs::File, sync::Arc};
+
+use arrow::{
+    datatypes::{FieldRef, Schema, SchemaRef},
+    record_batch::RecordBatch,
+};
+use parquet::arrow::ArrowWriter;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_arrow::schema::{SchemaLike, TracingOptions};
+
+use super::{OutputResult, ReportSink};
+
+const ROW_GROUP_SIZE: usize = 100_000;
+
+/// Writes rows to a Parquet file in row groups as they arrive. The key columns come first,
+/// followed by the item columns.
+pub struct ParquetWriter<K, T> {
+    writer: Option<ArrowWriter<File>>,
+    schema: SchemaRef,
+    key_fields: Vec<FieldRef>,
+    item_fields: Vec<FieldRef>,
+    keys: Vec<K>,
+    items: Vec<T>,
+}
+
+impl<K, T> ParquetWriter<K, T>
+where
+    K: Serialize,
+    T: Serialize + DeserializeOwned,
+{
+    /// Creates a writer whose key columns are traced from `keys`, the keys of every
+    /// scenario, since keys can carry columns that depend on the config and that are missing
+    /// in some scenarios
+    pub fn new(file: File, keys: &[K]) -> OutputResult<Self> {
+        // Item schemas are traced from the type rather than from rows, so a column keeps its
+        // type even if every value in the first row group is missing
+        let key_fields =
+            Vec::<FieldRef>::from_samples(keys, TracingOptions::default().allow_null_fields(true))?;
+        let item_fields = Vec::<FieldRef>::from_type::<T>(TracingOptions::default())?;
+        let schema = Arc::new(Schema::new(
+            key_fields
+                .iter()
+                .chain(item_fields.iter())
+                .cloned()
+                .collect::<Vec<_>>(),
+        ));
+        let writer = ArrowWriter::try_new(file, schema.clone(), None)?;
+        Ok(ParquetWriter {
+            writer: Some(writer),
+            schema,
+            key_fields,
+            item_fields,
+            keys: Vec::new(),
+            items: Vec::new(),
+        })
+    }
+
+    fn write_row_group(&mut self) -> OutputResult<()> {
+        if self.items.is_empty() {
+            return Ok(());
+        }
+        // The rows are dropped even if the row group can't be written, so an error is
+        // reported once rather than retried on every following row
+        let keys = std::mem::take(&mut self.keys);
+        let items = std::mem::take(&mut self.items);
+        let keys = serde_arrow::to_record_batch(&self.key_fields, &keys)?;
+        let items = serde_arrow::to_record_batch(&self.item_fields, &items)?;
+        let columns = keys
+            .columns()
+            .iter()
+            .chain(items.columns())
+            .cloned()
+            .collect();
+        let batch = RecordBatch::try_new(self.schema.clone(), columns)?;
+        let writer = self
+            .writer
+            .as_mut()
+            .expect("Parquet writer has already been finished");
+        writer.write(&batch)?;
+        // Flushing closes the row group, so each batch becomes one row group
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+impl<K, T> ReportSink<K, T> for ParquetWriter<K, T>
+where
+    K: Serialize,
+    T: Serialize + DeserializeOwned,
+{
+    fn write(&mut self, key: K, item: T) -> OutputResult<()> {
+        self.keys.push(key);
+        self.items.push(item);
+        if self.items.len() >= ROW_GROUP_SIZE {
+            self.write_row_group()?;
+        }
+        Ok(())
+    }
+
+    /// Writes any remaining rows and the file footer
+    fn finish(&mut self) -> OutputResult<()> {
+        self.write_row_group()?;
+        if let Some(writer) = self.writer.take() {
+            writer.close()?;
+        }
+        Ok(())
+    }
+}
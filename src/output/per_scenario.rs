@@ -0,0 +1,75 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::{create_file_sink, OutputFormat, OutputResult, ReportSink, ScenarioKey};
+
+/// Directory that a scenario's files are written to in the per-scenario layout
+pub fn get_scenario_path(path: &Path, scenario: usize) -> PathBuf {
+    path.join(format!("scenario_{}", scenario))
+}
+
+/// Writes each scenario's rows of a report to a file in that scenario's directory. Files are
+/// opened at a scenario's first row and closed once it has finished, so only the scenarios
+/// in progress hold files open, and a scenario without rows gets no file.
+pub struct PerScenarioSink<K, T> {
+    path: PathBuf,
+    name: String,
+    format: OutputFormat,
+    keys: Vec<K>,
+    open: BTreeMap<usize, Box<dyn ReportSink<K, T>>>,
+}
+
+impl<K, T> PerScenarioSink<K, T>
+where
+    K: Serialize + 'static,
+    T: Serialize + DeserializeOwned + 'static,
+{
+    pub fn new(path: &Path, name: &str, format: OutputFormat, keys: Vec<K>) -> Self {
+        PerScenarioSink {
+            path: path.to_path_buf(),
+            name: name.to_string(),
+            format,
+            keys,
+            open: BTreeMap::new(),
+        }
+    }
+
+    fn get_sink(&mut self, scenario: usize) -> OutputResult<&mut dyn ReportSink<K, T>> {
+        if !self.open.contains_key(&scenario) {
+            let scenario_path = get_scenario_path(&self.path, scenario);
+            fs::create_dir_all(&scenario_path)?;
+            let sink = create_file_sink(&scenario_path, &self.name, self.format, &self.keys);
+            self.open.insert(scenario, sink);
+        }
+        Ok(self.open.get_mut(&scenario).unwrap().as_mut())
+    }
+}
+
+impl<K, T> ReportSink<K, T> for PerScenarioSink<K, T>
+where
+    K: Serialize + ScenarioKey + 'static,
+    T: Serialize + DeserializeOwned + 'static,
+{
+    fn write(&mut self, key: K, item: T) -> OutputResult<()> {
+        self.get_sink(key.scenario())?.write(key, item)
+    }
+
+    fn finish_scenario(&mut self, scenario: usize) -> OutputResult<()> {
+        match self.open.remove(&scenario) {
+            Some(mut sink) => sink.finish(),
+            None => Ok(()),
+        }
+    }
+
+    fn finish(&mut self) -> OutputResult<()> {
+        for (_, mut sink) in std::mem::take(&mut self.open) {
+            sink.finish()?;
+        }
+        Ok(())
+    }
+}
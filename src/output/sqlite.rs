@@ -0,0 +1,156 @@
+use std::{fs, marker::PhantomData, path::Path, rc::Rc};
+
+use arrow::datatypes::{DataType, FieldRef};
+use rusqlite::{params_from_iter, types::Value, Connection};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_arrow::schema::{SchemaLike, TracingOptions};
+
+use super::{get_row_object, OutputResult, ReportSink};
+
+const BATCH_SIZE: usize = 10_000;
+
+/// A database holding one table per report
+pub struct SqliteDatabase {
+    connection: Connection,
+}
+
+impl SqliteDatabase {
+    pub fn create(path: &Path) -> OutputResult<Self> {
+        // Tables are created fresh, so replace the results of any previous run
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        let connection = Connection::open(path)?;
+        connection.execute_batch("PRAGMA journal_mode = WAL; PRAGMA synchronous = NORMAL;")?;
+        Ok(SqliteDatabase { connection })
+    }
+}
+
+fn get_column_type(data_type: &DataType) -> &'static str {
+    match data_type {
+        DataType::Boolean
+        | DataType::Int8
+        | DataType::Int16
+        | DataType::Int32
+        | DataType::Int64
+        | DataType::UInt8
+        | DataType::UInt16
+        | DataType::UInt32
+        | DataType::UInt64 => "INTEGER",
+        DataType::Float32 | DataType::Float64 => "REAL",
+        DataType::Utf8 | DataType::LargeUtf8 => "TEXT",
+        _ => "",
+    }
+}
+
+fn get_value(value: Option<&serde_json::Value>) -> Value {
+    match value {
+        None | Some(serde_json::Value::Null) => Value::Null,
+        Some(serde_json::Value::Bool(value)) => Value::Integer(*value as i64),
+        Some(serde_json::Value::Number(value)) => match value.as_i64() {
+            Some(value) => Value::Integer(value),
+            None => Value::Real(value.as_f64().unwrap_or(f64::NAN)),
+        },
+        Some(serde_json::Value::String(value)) => Value::Text(value.clone()),
+        Some(value) => Value::Text(value.to_string()),
+    }
+}
+
+/// Writes rows to one table of a `SqliteDatabase`, inserting them in batched transactions.
+/// The columns and their types are taken from the keys of every scenario and the item struct.
+pub struct SqliteTable<K, T> {
+    database: Rc<SqliteDatabase>,
+    fields: Vec<FieldRef>,
+    insert_sql: String,
+    rows: Vec<Vec<Value>>,
+    types: PhantomData<(K, T)>,
+}
+
+impl<K, T> SqliteTable<K, T>
+where
+    K: Serialize,
+    T: Serialize + DeserializeOwned,
+{
+    /// Creates the table, with key columns traced from `keys`
+    pub fn new(database: Rc<SqliteDatabase>, name: &str, keys: &[K]) -> OutputResult<Self> {
+        let fields =
+            Vec::<FieldRef>::from_samples(keys, TracingOptions::default().allow_null_fields(true))?
+                .into_iter()
+                .chain(Vec::<FieldRef>::from_type::<T>(TracingOptions::default())?)
+                .collect::<Vec<_>>();
+        let columns = fields
+            .iter()
+            .map(|field| {
+                format!(
+                    "\"{}\" {}",
+                    field.name(),
+                    get_column_type(field.data_type())
+                )
+            })
+            .collect::<Vec<_>>();
+        database.connection.execute_batch(&format!(
+            "CREATE TABLE \"{}\" ({});",
+            name,
+            columns.join(", ")
+        ))?;
+        if fields.iter().any(|field| field.name() == "scenario") {
+            database.connection.execute_batch(&format!(
+                "CREATE INDEX \"{0}_scenario\" ON \"{0}\" (scenario);",
+                name
+            ))?;
+        }
+        let insert_sql = format!(
+            "INSERT INTO \"{}\" VALUES ({})",
+            name,
+            vec!["?"; fields.len()].join(", ")
+        );
+        Ok(SqliteTable {
+            database,
+            fields,
+            insert_sql,
+            rows: Vec::new(),
+            types: PhantomData,
+        })
+    }
+
+    /// Inserts the buffered rows in a single transaction
+    fn flush(&mut self) -> OutputResult<()> {
+        if self.rows.is_empty() {
+            return Ok(());
+        }
+        // Tables share the connection, so the transaction cannot borrow it mutably
+        let transaction = self.database.connection.unchecked_transaction()?;
+        {
+            let mut statement = transaction.prepare_cached(&self.insert_sql)?;
+            for row in self.rows.drain(..) {
+                statement.execute(params_from_iter(row))?;
+            }
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+}
+
+impl<K, T> ReportSink<K, T> for SqliteTable<K, T>
+where
+    K: Serialize,
+    T: Serialize + DeserializeOwned,
+{
+    fn write(&mut self, key: K, item: T) -> OutputResult<()> {
+        let row = get_row_object(key, item)?;
+        self.rows.push(
+            self.fields
+                .iter()
+                .map(|field| get_value(row.get(field.name())))
+                .collect(),
+        );
+        if self.rows.len() >= BATCH_SIZE {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> OutputResult<()> {
+        self.flush()
+    }
+}
@@ -0,0 +1,322 @@
+use std::path::Path;
+
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use serde_derive::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+
+use super::{
+    parameters::{get_parameter_value, Experiment, OutputConfig, Parameters},
+    sweep::set_parameter,
+};
+
+/// Distribution a sampled parameter is drawn from
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(tag = "distribution", rename_all = "snake_case")]
+pub enum Prior {
+    Uniform {
+        min: f64,
+        max: f64,
+    },
+    LogUniform {
+        min: f64,
+        max: f64,
+    },
+    /// Uniform over the integers from `min` to `max` inclusive
+    UniformInteger {
+        min: i64,
+        max: i64,
+    },
+}
+
+impl Prior {
+    /// Maps a point of the unit interval to a value, so designs can sample the unit cube
+    pub fn get_quantile(&self, u: f64) -> Value {
+        match *self {
+            Prior::Uniform { min, max } => Value::from(min + u * (max - min)),
+            Prior::LogUniform { min, max } => Value::from((min.ln() + u * (max / min).ln()).exp()),
+            Prior::UniformInteger { min, max } => {
+                let value = min + (u * (max - min + 1) as f64).floor() as i64;
+                Value::from(value.min(max))
+            }
+        }
+    }
+
+    /// Converts a value to the parameter's type, rounding it for integer priors
+    pub fn get_value(&self, value: f64) -> Value {
+        match self {
+            Prior::UniformInteger { .. } => Value::from(value.round() as i64),
+            _ => Value::from(value),
+        }
+    }
+
+    /// Density, or for integer priors probability, of a value; zero outside the support
+    pub fn get_density(&self, value: f64) -> f64 {
+        match *self {
+            Prior::Uniform { min, max } if (min..=max).contains(&value) => 1.0 / (max - min),
+            Prior::LogUniform { min, max } if (min..=max).contains(&value) => {
+                1.0 / (value * (max / min).ln())
+            }
+            Prior::UniformInteger { min, max } if (min as f64..=max as f64).contains(&value) => {
+                1.0 / (max - min + 1) as f64
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SamplingMethod {
+    Random,
+    LatinHypercube,
+    Sobol,
+}
+
+/// How to sample scenarios from the parameter space
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Design {
+    pub method: SamplingMethod,
+    pub samples: usize,
+    /// Seeds random and Latin hypercube sampling; Sobol sequences are not scrambled
+    #[serde(default)]
+    pub seed: u64,
+    /// Priors of the sampled fields by dotted path, kept in the order they are listed
+    pub parameters: Mapping,
+}
+
+/// Parses priors given by dotted path, keeping the order they are listed in
+pub fn get_priors(parameters: &Mapping) -> Vec<(String, Prior)> {
+    parameters
+        .iter()
+        .map(|(field, prior)| {
+            let field = field
+                .as_str()
+                .expect("Sampled fields should be named by strings")
+                .to_string();
+            let prior = serde_yaml::from_value::<Prior>(prior.clone())
+                .unwrap_or_else(|e| panic!("Could not parse prior of {}: {}", field, e));
+            (field, prior)
+        })
+        .collect()
+}
+
+impl Design {
+    /// Points of the unit cube, one per sample
+    pub fn get_unit_points(&self, dimensions: usize) -> Vec<Vec<f64>> {
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        match self.method {
+            SamplingMethod::Random => (0..self.samples)
+                .map(|_| (0..dimensions).map(|_| rng.gen::<f64>()).collect())
+                .collect(),
+            SamplingMethod::LatinHypercube => {
+                get_latin_hypercube(self.samples, dimensions, &mut rng)
+            }
+            SamplingMethod::Sobol => {
+                let mut sequence = SobolSequence::new(dimensions);
+                (0..self.samples).map(|_| sequence.next_point()).collect()
+            }
+        }
+    }
+}
+
+/// One point in each of `samples` equal strata of every dimension, placed at random within
+/// its stratum
+pub fn get_latin_hypercube(samples: usize, dimensions: usize, rng: &mut StdRng) -> Vec<Vec<f64>> {
+    let mut points = vec![vec![0.0; dimensions]; samples];
+    for dimension in 0..dimensions {
+        let mut strata = (0..samples).collect::<Vec<_>>();
+        strata.shuffle(rng);
+        for (point, stratum) in points.iter_mut().zip(strata) {
+            point[dimension] = (stratum as f64 + rng.gen::<f64>()) / samples as f64;
+        }
+    }
+    points
+}
+
+// Degree, coefficients and initial direction numbers of the primitive polynomials for
+// dimensions 2 onwards, from Joe and Kuo's new-joe-kuo-6.21201
+const SOBOL_POLYNOMIALS: [(usize, u32, &[u32]); 20] = [
+    (1, 0, &[1]),
+    (2, 1, &[1, 3]),
+    (3, 1, &[1, 3, 1]),
+    (3, 2, &[1, 1, 1]),
+    (4, 1, &[1, 1, 3, 3]),
+    (4, 4, &[1, 3, 5, 13]),
+    (5, 2, &[1, 1, 5, 5, 17]),
+    (5, 4, &[1, 1, 5, 5, 5]),
+    (5, 7, &[1, 1, 7, 11, 19]),
+    (5, 11, &[1, 1, 5, 1, 1]),
+    (5, 13, &[1, 1, 1, 3, 11]),
+    (5, 14, &[1, 3, 5, 5, 31]),
+    (6, 1, &[1, 3, 3, 9, 7, 49]),
+    (6, 13, &[1, 1, 1, 15, 21, 21]),
+    (6, 16, &[1, 3, 1, 13, 27, 49]),
+    (6, 19, &[1, 1, 1, 15, 7, 5]),
+    (6, 22, &[1, 3, 1, 15, 13, 25]),
+    (6, 25, &[1, 1, 5, 5, 19, 61]),
+    (7, 1, &[1, 3, 7, 11, 23, 15, 103]),
+    (7, 4, &[1, 3, 7, 13, 13, 15, 69]),
+];
+
+pub const MAX_SOBOL_DIMENSIONS: usize = SOBOL_POLYNOMIALS.len() + 1;
+
+/// Sobol low-discrepancy sequence, generated in Gray code order. The first point, at the
+/// origin, is skipped.
+pub struct SobolSequence {
+    directions: Vec<[u32; 32]>,
+    point: Vec<u32>,
+    index: u32,
+}
+
+impl SobolSequence {
+    pub fn new(dimensions: usize) -> Self {
+        assert!(
+            dimensions <= MAX_SOBOL_DIMENSIONS,
+            "Sobol sequences are limited to {} dimensions",
+            MAX_SOBOL_DIMENSIONS
+        );
+        let mut directions = Vec::new();
+        if dimensions > 0 {
+            let mut first = [0; 32];
+            for (bit, direction) in first.iter_mut().enumerate() {
+                *direction = 1 << (31 - bit);
+            }
+            directions.push(first);
+        }
+        let polynomials = SOBOL_POLYNOMIALS.iter().take(dimensions.saturating_sub(1));
+        for &(degree, coefficients, initial) in polynomials {
+            let mut v = [0; 32];
+            for (bit, m) in initial.iter().enumerate() {
+                v[bit] = m << (31 - bit);
+            }
+            for bit in degree..32 {
+                v[bit] = v[bit - degree] ^ (v[bit - degree] >> degree);
+                for k in 1..degree {
+                    if (coefficients >> (degree - 1 - k)) & 1 == 1 {
+                        v[bit] ^= v[bit - k];
+                    }
+                }
+            }
+            directions.push(v);
+        }
+        SobolSequence {
+            directions,
+            point: vec![0; dimensions],
+            index: 0,
+        }
+    }
+
+    pub fn next_point(&mut self) -> Vec<f64> {
+        let bit = self.index.trailing_ones() as usize;
+        self.index += 1;
+        self.point
+            .iter_mut()
+            .zip(self.directions.iter())
+            .map(|(x, v)| {
+                *x ^= v[bit];
+                *x as f64 / 2f64.powi(32)
+            })
+            .collect()
+    }
+}
+
+/// Base parameters and a design that samples selected fields from their priors
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SampledExperiment {
+    #[serde(default)]
+    pub output: OutputConfig,
+    #[serde(default)]
+    pub replicates: Option<usize>,
+    #[serde(default)]
+    pub master_seed: Option<u64>,
+    pub base: Value,
+    pub design: Design,
+}
+
+/// Sets each sampled field of `base` to its prior's quantile at a point of the unit cube
+pub fn get_sampled_parameters(
+    base: &Value,
+    priors: &[(String, Prior)],
+    point: &[f64],
+) -> Parameters {
+    let mut parameters = base.clone();
+    for ((field, prior), u) in priors.iter().zip(point) {
+        set_parameter(&mut parameters, field, prior.get_quantile(*u));
+    }
+    serde_yaml::from_value(parameters)
+        .unwrap_or_else(|e| panic!("Invalid sampled parameters: {}", e))
+}
+
+impl SampledExperiment {
+    /// Draws a scenario for each sample of the design. Sampled fields are written as columns
+    /// in every report.
+    pub fn into_experiment(self) -> Experiment {
+        let priors = get_priors(&self.design.parameters);
+        let scenarios = self
+            .design
+            .get_unit_points(priors.len())
+            .iter()
+            .map(|point| get_sampled_parameters(&self.base, &priors, point))
+            .collect();
+        Experiment {
+            output: self.output,
+            parameter_columns: priors.into_iter().map(|(field, _)| field).collect(),
+            replicates: self.replicates,
+            master_seed: self.master_seed,
+            scenarios,
+        }
+    }
+}
+
+/// Writes `design.csv`, with the values of each parameter set's parameter columns
+pub fn write_design_matrix(output_path: &Path, experiment: &Experiment) {
+    let mut writer =
+        csv::Writer::from_path(output_path.join("design.csv")).expect("Could not create design");
+    let header = ["parameter_set"]
+        .into_iter()
+        .chain(experiment.parameter_columns.iter().map(String::as_str));
+    writer.write_record(header).expect("Could not write design");
+    for (parameter_set, parameters) in experiment.scenarios.iter().enumerate() {
+        let values = serde_json::to_value(parameters).expect("Could not serialize parameters");
+        let record =
+            [parameter_set.to_string()]
+                .into_iter()
+                .chain(experiment.parameter_columns.iter().map(
+                    |column| match get_parameter_value(&values, column) {
+                        serde_json::Value::String(value) => value,
+                        value => value.to_string(),
+                    },
+                ));
+        writer.write_record(record).expect("Could not write design");
+    }
+    writer.flush().expect("Could not write design");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sobol_sequence_matches_known_points() {
+        let mut sequence = SobolSequence::new(3);
+        let points = (0..4).map(|_| sequence.next_point()).collect::<Vec<_>>();
+        assert_eq!(points[0], vec![0.5, 0.5, 0.5]);
+        assert_eq!(points[1], vec![0.75, 0.25, 0.25]);
+        assert_eq!(points[2], vec![0.25, 0.75, 0.75]);
+        assert_eq!(points[3], vec![0.375, 0.375, 0.625]);
+    }
+
+    #[test]
+    fn test_latin_hypercube_fills_every_stratum() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let points = get_latin_hypercube(10, 3, &mut rng);
+        for dimension in 0..3 {
+            let mut strata = points
+                .iter()
+                .map(|point| (point[dimension] * 10.0).floor() as usize)
+                .collect::<Vec<_>>();
+            strata.sort();
+            assert_eq!(strata, (0..10).collect::<Vec<_>>());
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use std::{fs::File, path::Path};
+
+use chrono::{DateTime, Utc};
+use serde_derive::Serialize;
+
+use super::{parameters::Experiment, reports::ScenarioRun};
+
+/// Provenance of a run: how the outputs in the directory were produced
+#[derive(Debug, Serialize)]
+pub struct Manifest<'a> {
+    pub version: &'static str,
+    /// Commit the binary was built from, suffixed with `-dirty` if there were uncommitted
+    /// changes
+    pub git_commit: &'static str,
+    pub command: Vec<String>,
+    pub threads: u8,
+    /// The config with defaults filled in
+    pub config: &'a Experiment,
+    pub started: DateTime<Utc>,
+    pub finished: DateTime<Utc>,
+    pub scenarios: Vec<ScenarioRun>,
+}
+
+impl<'a> Manifest<'a> {
+    pub fn new(
+        config: &'a Experiment,
+        threads: u8,
+        started: DateTime<Utc>,
+        scenarios: Vec<ScenarioRun>,
+    ) -> Self {
+        Manifest {
+            version: env!("CARGO_PKG_VERSION"),
+            git_commit: env!("GIT_COMMIT"),
+            command: std::env::args().collect(),
+            threads,
+            config,
+            started,
+            finished: Utc::now(),
+            scenarios,
+        }
+    }
+
+    pub fn write(&self, output_path: &Path) {
+        let file = File::create(output_path.join("manifest.json"))
+            .expect("Could not create manifest file.");
+        serde_json::to_writer_pretty(file, self).expect("Could not write manifest");
+    }
+}
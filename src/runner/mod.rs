@@ -0,0 +1,522 @@
+pub mod design;
+pub mod manifest;
+pub mod parameters;
+pub mod reports;
+pub mod sweep;
+
+use std::{
+    fs::{self, File},
+    time::Instant,
+};
+
+use eosim::{
+    context::Context, global_properties::GlobalPropertyContext, random::RandomContext,
+    reports::Report,
+};
+use threadpool::ThreadPool;
+use tokio::runtime::Handle;
+use tokio::sync::mpsc::{self, Sender};
+
+use self::{
+    parameters::{Parameters, Scenario, ScenarioInfo, ScenarioParameters},
+    reports::{ReportRow, ReportSinks, ScenarioOrder, ScenarioRun},
+};
+use crate::output::{per_scenario::get_scenario_path, OutputFormat, ReportOutput, ReportSink};
+use crate::sir::{
+    aggregate_report::AggregateReport,
+    behavior_manager::BehaviorManager,
+    compartment_counts::CompartmentCounter,
+    compartment_report::CompartmentReport,
+    contact_tracing::ContactTracingManager,
+    death_manager::DeathManager,
+    death_report::DeathReport,
+    demography::DemographyManager,
+    global_properties::{
+        Behavior, CompartmentReportInterval, ContactTracing, DeathRate, Demography,
+        InfectiousPeriod, InitialInfections, Isolation, Population, ReportAggregation, RtInterval,
+        Testing, R0,
+    },
+    incidence_report::IncidenceReport,
+    infection_manager::InfectionManager,
+    infection_seeder::InfectionSeeder,
+    isolation_manager::IsolationManager,
+    line_list_report::LineListReport,
+    population_loader::PopulationLoader,
+    rt_report::{release_rt, RtReport},
+    summary_report::{release_summary, SummaryReport},
+    testing_manager::TestingManager,
+    transmission_manager::TransmissionManager,
+    transmission_tree_report::{release_newick_trees, TransmissionTreeReport},
+};
+
+pub fn setup_context(context: &mut Context, parameters: &Parameters) {
+    // Set up parameters in simulation
+    context.set_global_property_value::<Population>(parameters.population);
+    context.set_global_property_value::<R0>(parameters.r0);
+    context.set_global_property_value::<InfectiousPeriod>(parameters.infectious_period);
+    context.set_global_property_value::<InitialInfections>(parameters.initial_infections);
+    context.set_global_property_value::<DeathRate>(parameters.death_rate);
+    context.set_global_property_value::<CompartmentReportInterval>(
+        parameters.compartment_report_interval,
+    );
+
+    // Set up RNG
+    context.set_base_random_seed(parameters.random_seed);
+    for reseed in &parameters.reseed {
+        let random_seed = reseed.random_seed;
+        context.add_plan(reseed.time, move |context| {
+            context.set_base_random_seed(random_seed);
+        });
+    }
+
+    // Add reports
+    // Infections and deaths are reported either per event or as binned counts
+    if let Some(report_aggregation) = parameters.report_aggregation {
+        context.set_global_property_value::<ReportAggregation>(report_aggregation);
+        context.add_component::<AggregateReport>();
+    } else {
+        context.add_component::<IncidenceReport>();
+        context.add_component::<DeathReport>();
+    }
+    context.add_component::<CompartmentCounter>();
+    context.add_component::<CompartmentReport>();
+    context.add_component::<SummaryReport>();
+    if let Some(rt_interval) = parameters.rt_interval {
+        context.set_global_property_value::<RtInterval>(rt_interval);
+        context.add_component::<RtReport>();
+    }
+    if parameters.line_list {
+        context.add_component::<LineListReport>();
+    }
+    if parameters.transmission_tree.is_some() {
+        context.add_component::<TransmissionTreeReport>();
+    }
+
+    // Add optional interventions (before seeding, so initial infections are observed)
+    if let Some(isolation) = parameters.isolation {
+        context.set_global_property_value::<Isolation>(isolation);
+        context.add_component::<IsolationManager>();
+    }
+    if let Some(contact_tracing) = parameters.contact_tracing {
+        assert!(
+            parameters.isolation.is_some(),
+            "Contact tracing requires isolation parameters"
+        );
+        context.set_global_property_value::<ContactTracing>(contact_tracing);
+        context.add_component::<ContactTracingManager>();
+    }
+    if let Some(testing) = &parameters.testing {
+        context.set_global_property_value::<Testing>(testing.clone());
+        context.add_component::<TestingManager>();
+    }
+    if let Some(behavior) = parameters.behavior {
+        context.set_global_property_value::<Behavior>(behavior);
+        context.add_component::<BehaviorManager>();
+    }
+
+    // Add model components
+    context.add_component::<PopulationLoader>();
+    context.add_component::<InfectionManager>();
+    context.add_component::<TransmissionManager>();
+    context.add_component::<InfectionSeeder>();
+    context.add_component::<DeathManager>();
+
+    // Add demographic turnover (after the population and contact index exist)
+    if let Some(demography) = &parameters.demography {
+        context.set_global_property_value::<Demography>(demography.clone());
+        context.add_component::<DemographyManager>();
+    }
+}
+
+// Release reports that summarize the whole simulation once it has finished
+pub fn release_final_reports(context: &mut Context, parameters: &Parameters) {
+    release_summary(context);
+    if parameters.rt_interval.is_some() {
+        release_rt(context);
+    }
+    if let Some(transmission_tree) = parameters.transmission_tree {
+        release_newick_trees(context, transmission_tree.newick_sample_size);
+    }
+}
+
+/// Writes the parameters of every scenario to a table that results can be joined with
+pub fn write_scenario_table(output: &ReportOutput, scenarios: &[ScenarioInfo]) {
+    if scenarios.is_empty() {
+        return;
+    }
+    let keys = scenarios
+        .iter()
+        .map(ScenarioInfo::get_parameter_key)
+        .collect::<Vec<_>>();
+    let mut sink = output.create_sink::<Scenario, ScenarioParameters>(
+        "scenarios",
+        OutputFormat::Sqlite,
+        &keys,
+    );
+    for info in scenarios {
+        let scenario_parameters = ScenarioParameters {
+            parameters: serde_json::to_string(&info.parameters)
+                .expect("Could not serialize parameters"),
+        };
+        if let Err(e) = sink.write(info.get_parameter_key(), scenario_parameters) {
+            eprintln!("{}", e);
+        }
+    }
+    if let Err(e) = sink.finish() {
+        eprintln!("{}", e);
+    }
+}
+
+/// Writes each scenario's parameters to its directory in the per-scenario layout, so that
+/// the scenario can be re-run on its own
+pub fn write_scenario_parameters(output: &ReportOutput, scenarios: &[ScenarioInfo]) {
+    for info in scenarios {
+        let scenario_path = get_scenario_path(output.path(), info.scenario);
+        fs::create_dir_all(&scenario_path).expect("Could not create scenario directory");
+        let parameters_file = File::create(scenario_path.join("parameters.yaml"))
+            .expect("Could not create scenario parameters file");
+        serde_yaml::to_writer(parameters_file, &info.parameters)
+            .expect("Could not write scenario parameters");
+    }
+}
+
+// Merge this function into eosim
+pub fn get_bounded_channel_report_handler<T: Report, S>(
+    sender: Sender<(S, T::Item)>,
+    id: S,
+) -> impl FnMut(T::Item) + 'static
+where
+    T::Item: Send + 'static,
+    S: Send + Copy + 'static,
+{
+    move |item| {
+        let sender = sender.clone();
+        let id = id;
+        futures::executor::block_on(async move {
+            if let Err(e) = sender.send((id, item)).await {
+                panic!("Due to receiver being closed, failed to send item: {:?}", e);
+            }
+        });
+    }
+}
+
+pub fn run_single_threaded(scenarios: &[ScenarioInfo], sinks: ReportSinks) -> Vec<ScenarioRun> {
+    for info in scenarios {
+        // Set up and execute context
+        let scenario = info.scenario;
+        let start = Instant::now();
+        let mut context = Context::new();
+        sinks.set_handlers(&mut context, scenario);
+        setup_context(&mut context, &info.parameters);
+        context.execute();
+        release_final_reports(&mut context, &info.parameters);
+        sinks.finish_scenario(scenario, start.elapsed().as_secs_f64());
+        eprintln!("Scenario {} completed", scenario);
+    }
+    sinks.finish()
+}
+
+/// Runs scenarios on `threads` threads. With `ordered`, output is identical to that of
+/// `run_single_threaded`; otherwise rows are written as they arrive, which holds less in
+/// memory but interleaves scenarios.
+pub async fn run_multi_threaded(
+    scenarios: &[ScenarioInfo],
+    sinks: ReportSinks,
+    threads: u8,
+    ordered: bool,
+) -> Vec<ScenarioRun> {
+    let pool = ThreadPool::new(threads.into());
+    let (sender, mut receiver) = mpsc::channel::<ReportRow>(100000);
+    let handlers = sinks.get_channel_handlers();
+
+    let handle = Handle::current();
+
+    for info in scenarios {
+        let scenario = info.scenario;
+        let sender = sender.clone();
+        let handlers = handlers.clone();
+        let parameters = info.parameters.clone();
+        let handle = handle.clone();
+        pool.execute(move || {
+            let _guard = handle.enter();
+            // Set up and execute context
+            let start = Instant::now();
+            let mut context = Context::new();
+            handlers.set_handlers(&mut context, scenario, &sender);
+            setup_context(&mut context, &parameters);
+            context.execute();
+            release_final_reports(&mut context, &parameters);
+            handlers.finish_scenario(scenario, start.elapsed().as_secs_f64(), &sender);
+            eprintln!("Scenario {} completed", scenario);
+        });
+    }
+    drop(sender);
+
+    // Write output from main thread
+    let mut order = ScenarioOrder::new();
+    while let Some(row) = receiver.recv().await {
+        if ordered {
+            order.write(&sinks, row);
+        } else {
+            sinks.write(row);
+        }
+    }
+    sinks.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::MemorySink;
+    use crate::runner::parameters::{Experiment, OutputConfig, Scenario};
+    use crate::runner::reports::add_report_sinks;
+    use crate::sir::{incidence_report::Infection, summary_report::RunSummary};
+    use serde_derive::Serialize;
+    use std::collections::BTreeSet;
+    use std::path::Path;
+    use std::sync::Arc;
+    use tokio::sync::mpsc;
+    use tokio::sync::Mutex;
+    use tokio::time::{sleep, Duration};
+
+    #[tokio::test]
+    async fn test_backpressure_on_channel() {
+        println!("Test started");
+
+        let (sender, mut receiver) = mpsc::channel::<(u32, i32)>(2); // Create buffer
+        println!("Channel created");
+
+        let mut handler = get_bounded_channel_report_handler::<DummyReport, u32>(sender, 42); // Create buffer
+        println!("Handler created");
+
+        let stop_flag = Arc::new(Mutex::new(false)); // Flag determines whether the consumer should block
+        let stop_flag_clone = stop_flag.clone();
+
+        // Create a consumer that will temporarily block
+        let consumer = tokio::spawn(async move {
+            println!("Consumer started");
+            while let Some((_id, item)) = receiver.recv().await {
+                // Wait to receive messages from channel
+                println!("Received: {}", item);
+                let flag = stop_flag_clone.lock().await;
+                if *flag {
+                    // If stop_flag is true, consumer should simulate delay by sleeping for one second
+                    println!("Consumer blocking");
+                    sleep(Duration::from_secs(1)).await; // Simulate a delay
+                }
+            }
+            println!("Consumer done");
+        });
+
+        // Fill the channel
+        handler(1);
+        println!("Handler 1 sent");
+        handler(2);
+        println!("Handler 2 sent");
+        println!("Channel filled");
+
+        // The channel should now be full, and the next send should block
+        let producer = tokio::spawn(async move {
+            handler(3); // This should block until the consumer drains the channel
+            println!("Handler 3 sent");
+            handler(4);
+            println!("Handler 4 sent");
+        });
+
+        // Now, let the producer block for a while
+        sleep(Duration::from_millis(500)).await;
+
+        // Check that the producer has not been able to send the third message yet
+        {
+            let flag = stop_flag.lock().await;
+            assert!(*flag == false, "Producer should be blocked, but it is not");
+            println!("Producer is blocked as expected");
+        }
+
+        // Allow the consumer to drain the channel
+        {
+            let mut flag = stop_flag.lock().await;
+            *flag = true;
+        }
+
+        // Wait for the tasks to complete
+        consumer.await.unwrap();
+        producer.await.unwrap();
+        println!("Test completed");
+    }
+
+    fn get_test_parameters(random_seed: u64) -> Parameters {
+        serde_yaml::from_str(&format!(
+            "population: 1000
+r0: 2.0
+infectious_period: 3.0
+initial_infections: 5
+random_seed: {}
+death_rate: 0.1",
+            random_seed
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_memory_sinks_receive_every_scenario() {
+        let parameters_vec = vec![get_test_parameters(1), get_test_parameters(2)];
+        let scenarios = Experiment {
+            scenarios: parameters_vec.clone(),
+            ..Default::default()
+        }
+        .get_scenario_infos();
+        let summaries = MemorySink::<Scenario, RunSummary>::new();
+        let infections = MemorySink::<Scenario, Infection>::new();
+
+        // Discard every report except the two under test
+        let mut sinks = ReportSinks::new(&scenarios);
+        let output_config = OutputConfig {
+            format: Some(OutputFormat::Null),
+            ..Default::default()
+        };
+        add_report_sinks(
+            &mut sinks,
+            &ReportOutput::new(Path::new(".")),
+            &output_config,
+            OutputFormat::Null,
+        );
+        sinks.add::<SummaryReport, Scenario>(
+            "summary",
+            Box::new(summaries.clone()),
+            ScenarioInfo::get_parameter_key,
+        );
+        sinks.add::<IncidenceReport, Scenario>(
+            "incidence_report",
+            Box::new(infections.clone()),
+            ScenarioInfo::get_key,
+        );
+        run_single_threaded(&scenarios, sinks);
+
+        let summaries = summaries.rows();
+        assert_eq!(summaries.len(), 2);
+        for (scenario, (key, summary)) in summaries.iter().enumerate() {
+            assert_eq!(key.scenario, scenario);
+            assert_eq!(
+                key.get_column("random_seed"),
+                Some(&serde_json::json!(parameters_vec[scenario].random_seed))
+            );
+            let scenario_infections = infections
+                .rows()
+                .iter()
+                .filter(|(key, _)| key.scenario == scenario)
+                .count();
+            assert_eq!(scenario_infections, summary.total_infections);
+        }
+    }
+
+    #[test]
+    fn test_only_enabled_reports_get_sinks() {
+        let mut line_list_parameters = get_test_parameters(2);
+        line_list_parameters.line_list = true;
+        let scenarios = Experiment {
+            scenarios: vec![get_test_parameters(1), line_list_parameters],
+            ..Default::default()
+        }
+        .get_scenario_infos();
+        let mut sinks = ReportSinks::new(&scenarios);
+        add_report_sinks(
+            &mut sinks,
+            &ReportOutput::new(Path::new(".")),
+            &OutputConfig::default(),
+            OutputFormat::Null,
+        );
+        assert_eq!(
+            sinks.report_names(),
+            [
+                "incidence_report",
+                "death_report",
+                "compartment_report",
+                "line_list_report",
+                "summary"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_replicates_get_derived_seeds() {
+        let experiment = Experiment {
+            replicates: Some(3),
+            master_seed: Some(42),
+            scenarios: vec![get_test_parameters(1), get_test_parameters(1)],
+            ..Default::default()
+        };
+        let scenarios = experiment.get_scenario_infos();
+        assert_eq!(scenarios.len(), 6);
+        let seeds = scenarios
+            .iter()
+            .map(|info| info.parameters.random_seed)
+            .collect::<BTreeSet<_>>();
+        assert_eq!(seeds.len(), 6);
+        assert_eq!(
+            scenarios[4].parameters.random_seed,
+            experiment.get_scenario_infos()[4].parameters.random_seed
+        );
+        let key = scenarios[4].get_key();
+        assert_eq!(key.get_column("parameter_set"), Some(&serde_json::json!(1)));
+        assert_eq!(key.get_column("replicate"), Some(&serde_json::json!(1)));
+    }
+
+    #[tokio::test]
+    async fn test_ordered_multi_threaded_output_matches_single_threaded() {
+        let scenarios = Experiment {
+            scenarios: (0..4).map(get_test_parameters).collect(),
+            ..Default::default()
+        }
+        .get_scenario_infos();
+        let base_path =
+            std::env::temp_dir().join(format!("eosim_demo_ordered_{}", std::process::id()));
+        let single_path = base_path.join("single");
+        let multi_path = base_path.join("multi");
+        fs::create_dir_all(&single_path).unwrap();
+        fs::create_dir_all(&multi_path).unwrap();
+
+        let output = ReportOutput::new(&single_path);
+        let mut sinks = ReportSinks::new(&scenarios);
+        add_report_sinks(
+            &mut sinks,
+            &output,
+            &OutputConfig::default(),
+            OutputFormat::Csv,
+        );
+        run_single_threaded(&scenarios, sinks);
+
+        let output = ReportOutput::new(&multi_path);
+        let mut sinks = ReportSinks::new(&scenarios);
+        add_report_sinks(
+            &mut sinks,
+            &output,
+            &OutputConfig::default(),
+            OutputFormat::Csv,
+        );
+        run_multi_threaded(&scenarios, sinks, 4, true).await;
+
+        let mut file_names = fs::read_dir(&single_path)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect::<Vec<_>>();
+        file_names.sort();
+        assert!(!file_names.is_empty());
+        for file_name in file_names {
+            assert_eq!(
+                fs::read(single_path.join(&file_name)).unwrap(),
+                fs::read(multi_path.join(&file_name)).unwrap(),
+                "{:?} differs between runs",
+                file_name
+            );
+        }
+        fs::remove_dir_all(&base_path).unwrap();
+    }
+
+    #[derive(Serialize)]
+    struct DummyReport;
+
+    impl Report for DummyReport {
+        type Item = i32;
+    }
+}
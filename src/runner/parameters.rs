@@ -0,0 +1,292 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::{Arc, Mutex},
+};
+
+use serde::{ser::SerializeStruct, Serializer};
+use serde_derive::{Deserialize, Serialize};
+
+use super::{design::SampledExperiment, sweep::Sweep};
+use crate::output::{OutputFormat, OutputLayout, ScenarioKey};
+use crate::sir::{
+    aggregate_report::ReportAggregationParameters, behavior_manager::BehaviorParameters,
+    contact_tracing::ContactTracingParameters, demography::DemographyParameters,
+    isolation_manager::IsolationParameters, testing_manager::TestingParameters,
+    transmission_tree_report::TransmissionTreeParameters,
+};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Parameters {
+    pub population: usize,
+    pub r0: f64,
+    pub infectious_period: f64,
+    pub initial_infections: usize,
+    pub random_seed: u64,
+    pub death_rate: f64,
+    pub isolation: Option<IsolationParameters>,
+    pub contact_tracing: Option<ContactTracingParameters>,
+    pub testing: Option<TestingParameters>,
+    pub behavior: Option<BehaviorParameters>,
+    pub demography: Option<DemographyParameters>,
+    #[serde(default = "default_compartment_report_interval")]
+    pub compartment_report_interval: f64,
+    pub report_aggregation: Option<ReportAggregationParameters>,
+    #[serde(default)]
+    pub line_list: bool,
+    pub transmission_tree: Option<TransmissionTreeParameters>,
+    pub rt_interval: Option<f64>,
+    /// Replaces the random seed at given times, so that runs can be replayed segment by segment
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reseed: Vec<Reseed>,
+}
+
+fn default_compartment_report_interval() -> f64 {
+    1.0
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct Reseed {
+    pub time: f64,
+    pub random_seed: u64,
+}
+
+/// Scalar parameters written as columns in the summary and scenario table
+const SCALAR_PARAMETERS: [&str; 6] = [
+    "population",
+    "r0",
+    "infectious_period",
+    "initial_infections",
+    "random_seed",
+    "death_rate",
+];
+
+/// Identifies the scenario of a report row: its index followed by parameter columns
+#[derive(Debug, Clone)]
+pub struct Scenario {
+    pub scenario: usize,
+    columns: Arc<Vec<(Arc<str>, serde_json::Value)>>,
+}
+
+impl Scenario {
+    pub fn get_column(&self, name: &str) -> Option<&serde_json::Value> {
+        self.columns
+            .iter()
+            .find(|(column, _)| &**column == name)
+            .map(|(_, value)| value)
+    }
+}
+
+// Serialized struct fields need static names, so each distinct column name is leaked once
+// however many experiments are run
+fn get_static_name(name: &str) -> &'static str {
+    static NAMES: Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+    let mut names = NAMES.lock().unwrap();
+    if let Some(&static_name) = names.get(name) {
+        return static_name;
+    }
+    let static_name = &*Box::leak(name.to_string().into_boxed_str());
+    names.insert(static_name);
+    static_name
+}
+
+// Serialized as a flat struct, since CSV and Arrow can't take maps
+impl serde::Serialize for Scenario {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Scenario", 1 + self.columns.len())?;
+        state.serialize_field("scenario", &self.scenario)?;
+        for (name, value) in self.columns.iter() {
+            state.serialize_field(get_static_name(name), value)?;
+        }
+        state.end()
+    }
+}
+
+impl ScenarioKey for Scenario {
+    fn scenario(&self) -> usize {
+        self.scenario
+    }
+}
+
+/// A scenario's parameters and the keys its report rows carry
+#[derive(Debug, Clone)]
+pub struct ScenarioInfo {
+    pub scenario: usize,
+    pub parameters: Parameters,
+    key: Scenario,
+    parameter_key: Scenario,
+}
+
+impl ScenarioInfo {
+    /// The scenario index and any parameter columns of the experiment
+    pub fn get_key(&self) -> Scenario {
+        self.key.clone()
+    }
+
+    /// Also holds the scalar parameters; used for the summary and the scenario table
+    pub fn get_parameter_key(&self) -> Scenario {
+        self.parameter_key.clone()
+    }
+}
+
+// SplitMix64, which spreads nearby seeds and indices over unrelated streams
+fn split_mix(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Derives the random seed of a replicate from the master seed and its indices
+pub fn get_replicate_seed(master_seed: u64, parameter_set: usize, replicate: usize) -> u64 {
+    split_mix(split_mix(master_seed ^ split_mix(parameter_set as u64)) ^ replicate as u64)
+}
+
+// Looks up a parameter by its dotted path, such as `isolation.detection_probability`
+pub(super) fn get_parameter_value(parameters: &serde_json::Value, path: &str) -> serde_json::Value {
+    let mut value = parameters;
+    for (depth, field) in path.split('.').enumerate() {
+        value = match value.get(field) {
+            Some(field_value) => field_value,
+            None if depth == 0 => panic!("Unknown parameter column {}", path),
+            None => return serde_json::Value::Null,
+        };
+    }
+    value.clone()
+}
+
+/// Full parameters of a scenario as JSON, including the optional feature blocks
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ScenarioParameters {
+    pub parameters: String,
+}
+
+/// Selects the format each report is written in
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct OutputConfig {
+    /// Format of reports not listed in `reports`; defaults to the command line format
+    #[serde(default)]
+    pub format: Option<OutputFormat>,
+    #[serde(default)]
+    pub reports: BTreeMap<String, OutputFormat>,
+    /// Defaults to the command line layout
+    #[serde(default)]
+    pub layout: Option<OutputLayout>,
+    /// Write multi-threaded output in scenario order, as a single thread would
+    #[serde(default)]
+    pub ordered: bool,
+}
+
+impl OutputConfig {
+    pub fn get_format(&self, report: &str, default_format: OutputFormat) -> OutputFormat {
+        self.reports
+            .get(report)
+            .copied()
+            .or(self.format)
+            .unwrap_or(default_format)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct Experiment {
+    #[serde(default)]
+    pub output: OutputConfig,
+    /// Parameters, named by dotted path, written as columns in every report
+    #[serde(default)]
+    pub parameter_columns: Vec<String>,
+    /// Runs each parameter set this many times, with seeds derived from `master_seed`
+    #[serde(default)]
+    pub replicates: Option<usize>,
+    /// Defaults to each parameter set's own `random_seed`
+    #[serde(default)]
+    pub master_seed: Option<u64>,
+    pub scenarios: Vec<Parameters>,
+}
+
+impl Experiment {
+    /// Describes each scenario to run. With replicates, each parameter set is run as that
+    /// many consecutive scenarios, and every report row carries its `parameter_set` and
+    /// `replicate`.
+    pub fn get_scenario_infos(&self) -> Vec<ScenarioInfo> {
+        let parameter_columns = self
+            .parameter_columns
+            .iter()
+            .map(|column| Arc::from(column.as_str()))
+            .collect::<Vec<Arc<str>>>();
+        let summary_columns = SCALAR_PARAMETERS
+            .iter()
+            .map(|column| Arc::from(*column))
+            .chain(
+                parameter_columns
+                    .iter()
+                    .filter(|column| !SCALAR_PARAMETERS.contains(&column.as_ref()))
+                    .cloned(),
+            )
+            .collect::<Vec<Arc<str>>>();
+
+        let mut scenarios = Vec::new();
+        for (parameter_set, parameters) in self.scenarios.iter().enumerate() {
+            for replicate in 0..self.replicates.unwrap_or(1) {
+                let mut parameters = parameters.clone();
+                let mut replicate_columns = Vec::new();
+                if self.replicates.is_some() {
+                    let master_seed = self.master_seed.unwrap_or(parameters.random_seed);
+                    parameters.random_seed =
+                        get_replicate_seed(master_seed, parameter_set, replicate);
+                    replicate_columns
+                        .push((Arc::from("parameter_set"), serde_json::json!(parameter_set)));
+                    replicate_columns.push((Arc::from("replicate"), serde_json::json!(replicate)));
+                }
+                let scenario = scenarios.len();
+                let values =
+                    serde_json::to_value(&parameters).expect("Could not serialize parameters");
+                let get_key = |columns: &[Arc<str>]| Scenario {
+                    scenario,
+                    columns: Arc::new(
+                        replicate_columns
+                            .iter()
+                            .cloned()
+                            .chain(columns.iter().map(|column| {
+                                (column.clone(), get_parameter_value(&values, column))
+                            }))
+                            .collect(),
+                    ),
+                };
+                scenarios.push(ScenarioInfo {
+                    scenario,
+                    key: get_key(&parameter_columns),
+                    parameter_key: get_key(&summary_columns),
+                    parameters,
+                });
+            }
+        }
+        scenarios
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Config {
+    Experiment(Experiment),
+    Sweep(Sweep),
+    Sampled(SampledExperiment),
+    Single(Parameters),
+    Multiple(Vec<Parameters>),
+}
+
+impl Config {
+    pub fn into_experiment(self) -> Experiment {
+        match self {
+            Config::Experiment(experiment) => experiment,
+            Config::Sweep(sweep) => sweep.into_experiment(),
+            Config::Sampled(sampled) => sampled.into_experiment(),
+            Config::Single(parameters) => Experiment {
+                scenarios: vec![parameters],
+                ..Default::default()
+            },
+            Config::Multiple(scenarios) => Experiment {
+                scenarios,
+                ..Default::default()
+            },
+        }
+    }
+}
@@ -0,0 +1,460 @@
+use std::{
+    any::Any,
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet},
+    marker::PhantomData,
+    rc::Rc,
+    sync::Arc,
+};
+
+use eosim::{
+    context::Context,
+    reports::{Report, ReportsContext},
+};
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::sync::mpsc::Sender;
+
+use super::{
+    get_bounded_channel_report_handler,
+    parameters::{OutputConfig, Parameters, Scenario, ScenarioInfo},
+};
+use crate::output::{OutputFormat, ReportOutput, ReportSink, ScenarioKey};
+use crate::sir::{
+    aggregate_report::{DeathCountReport, IncidenceCountReport},
+    compartment_report::CompartmentReport,
+    death_report::DeathReport,
+    incidence_report::IncidenceReport,
+    line_list_report::LineListReport,
+    observed_case_report::ObservedCaseReport,
+    rt_report::RtReport,
+    summary_report::SummaryReport,
+    tracing_report::TracingReport,
+    transmission_tree_report::{NewickTreeReport, TransmissionTreeReport},
+};
+
+/// Identifies the report and scenario that a row belongs to
+#[derive(Debug, Clone, Copy)]
+pub enum RowKey {
+    Item {
+        report: usize,
+        scenario: usize,
+    },
+    /// Sent once a scenario has released all of its items, with its wall time in seconds as
+    /// the item
+    ScenarioEnd {
+        scenario: usize,
+    },
+}
+
+pub type ReportRow = (RowKey, Box<dyn Any + Send>);
+
+/// Stands in for every report on the channel that multi-threaded runs send rows over
+pub struct AnyReport {}
+
+impl Report for AnyReport {
+    type Item = Box<dyn Any + Send>;
+}
+
+// A sink for one report, which recovers the item type and the scenario's key
+trait ScenarioSink {
+    fn write(&mut self, scenario: usize, item: Box<dyn Any + Send>);
+    fn finish_scenario(&mut self, scenario: usize);
+    fn finish(&mut self);
+    fn get_row_count(&self, scenario: usize) -> usize;
+}
+
+struct KeyedSink<K, T> {
+    name: String,
+    sink: Box<dyn ReportSink<K, T>>,
+    keys: Vec<K>,
+    row_counts: Vec<usize>,
+}
+
+impl<K: Clone, T: 'static> ScenarioSink for KeyedSink<K, T> {
+    fn write(&mut self, scenario: usize, item: Box<dyn Any + Send>) {
+        let item = *item
+            .downcast::<T>()
+            .unwrap_or_else(|_| panic!("Item of the wrong type sent to {}", self.name));
+        self.row_counts[scenario] += 1;
+        if let Err(e) = self.sink.write(self.keys[scenario].clone(), item) {
+            eprintln!("{}: {}", self.name, e);
+        }
+    }
+
+    fn finish_scenario(&mut self, scenario: usize) {
+        if let Err(e) = self.sink.finish_scenario(scenario) {
+            eprintln!("{}: {}", self.name, e);
+        }
+    }
+
+    fn finish(&mut self) {
+        if let Err(e) = self.sink.finish() {
+            eprintln!("{}: {}", self.name, e);
+        }
+    }
+
+    fn get_row_count(&self, scenario: usize) -> usize {
+        self.row_counts[scenario]
+    }
+}
+
+type SharedSinks = Rc<RefCell<Vec<Box<dyn ScenarioSink>>>>;
+
+// Sets the report item handler for one report type
+trait HandlerSetter: Send + Sync {
+    fn set_sink_handler(&self, context: &mut Context, key: RowKey, sinks: SharedSinks);
+    fn set_channel_handler(&self, context: &mut Context, key: RowKey, sender: Sender<ReportRow>);
+}
+
+struct ReportHandlerSetter<R>(PhantomData<fn() -> R>);
+
+impl<R: Report + 'static> HandlerSetter for ReportHandlerSetter<R>
+where
+    R::Item: Send + 'static,
+{
+    fn set_sink_handler(&self, context: &mut Context, key: RowKey, sinks: SharedSinks) {
+        context.set_report_item_handler::<R>(move |item| {
+            if let RowKey::Item { report, scenario } = key {
+                sinks.borrow_mut()[report].write(scenario, Box::new(item));
+            }
+        });
+    }
+
+    fn set_channel_handler(&self, context: &mut Context, key: RowKey, sender: Sender<ReportRow>) {
+        let mut handler = get_bounded_channel_report_handler::<AnyReport, RowKey>(sender, key);
+        context.set_report_item_handler::<R>(move |item| handler(Box::new(item)));
+    }
+}
+
+/// Handlers that send the rows of every report to the thread that owns the sinks
+#[derive(Clone)]
+pub struct ChannelHandlers {
+    setters: Vec<Arc<dyn HandlerSetter>>,
+}
+
+impl ChannelHandlers {
+    pub fn set_handlers(&self, context: &mut Context, scenario: usize, sender: &Sender<ReportRow>) {
+        for (report, setter) in self.setters.iter().enumerate() {
+            setter.set_channel_handler(context, RowKey::Item { report, scenario }, sender.clone());
+        }
+    }
+
+    /// Marks the end of a scenario's rows on the channel
+    pub fn finish_scenario(&self, scenario: usize, wall_time: f64, sender: &Sender<ReportRow>) {
+        let mut handler = get_bounded_channel_report_handler::<AnyReport, RowKey>(
+            sender.clone(),
+            RowKey::ScenarioEnd { scenario },
+        );
+        handler(Box::new(wall_time));
+    }
+}
+
+/// How one scenario ran, as recorded in the manifest
+#[derive(Debug, Serialize)]
+pub struct ScenarioRun {
+    pub scenario: usize,
+    pub random_seed: u64,
+    /// Seconds taken to run the scenario, if it finished
+    pub wall_time: Option<f64>,
+    /// Number of rows written to each report
+    pub row_counts: BTreeMap<String, usize>,
+}
+
+/// The sink that each report is written to, shared by all scenarios of a run
+pub struct ReportSinks {
+    scenarios: Vec<ScenarioInfo>,
+    names: Vec<String>,
+    setters: Vec<Arc<dyn HandlerSetter>>,
+    sinks: SharedSinks,
+    wall_times: RefCell<Vec<Option<f64>>>,
+}
+
+impl ReportSinks {
+    pub fn new(scenarios: &[ScenarioInfo]) -> Self {
+        // Sinks trace their key columns from the keys of the scenarios
+        assert!(
+            !scenarios.is_empty(),
+            "No scenarios to run; check for empty scenario lists, sweeps or designs"
+        );
+        ReportSinks {
+            scenarios: scenarios.to_vec(),
+            names: Vec::new(),
+            setters: Vec::new(),
+            sinks: Rc::new(RefCell::new(Vec::new())),
+            wall_times: RefCell::new(vec![None; scenarios.len()]),
+        }
+    }
+
+    /// Writes report `R` to `sink`, keying each row with `get_key` applied to its scenario.
+    /// Replaces any sink already added for a report of the same name.
+    pub fn add<R, K>(
+        &mut self,
+        name: &str,
+        sink: Box<dyn ReportSink<K, R::Item>>,
+        get_key: fn(&ScenarioInfo) -> K,
+    ) where
+        R: Report + 'static,
+        R::Item: Send + 'static,
+        K: Clone + 'static,
+    {
+        let setter: Arc<dyn HandlerSetter> = Arc::new(ReportHandlerSetter::<R>(PhantomData));
+        let sink: Box<dyn ScenarioSink> = Box::new(KeyedSink {
+            name: name.to_string(),
+            sink,
+            keys: self.scenarios.iter().map(get_key).collect(),
+            row_counts: vec![0; self.scenarios.len()],
+        });
+        match self.names.iter().position(|existing| existing == name) {
+            Some(report) => {
+                self.setters[report] = setter;
+                self.sinks.borrow_mut()[report] = sink;
+            }
+            None => {
+                self.names.push(name.to_string());
+                self.setters.push(setter);
+                self.sinks.borrow_mut().push(sink);
+            }
+        }
+    }
+
+    pub fn report_names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Sets handlers that write each report straight to its sink
+    pub fn set_handlers(&self, context: &mut Context, scenario: usize) {
+        for (report, setter) in self.setters.iter().enumerate() {
+            setter.set_sink_handler(
+                context,
+                RowKey::Item { report, scenario },
+                self.sinks.clone(),
+            );
+        }
+    }
+
+    pub fn get_channel_handlers(&self) -> ChannelHandlers {
+        ChannelHandlers {
+            setters: self.setters.clone(),
+        }
+    }
+
+    pub fn write(&self, (key, item): ReportRow) {
+        match key {
+            RowKey::Item { report, scenario } => {
+                self.sinks.borrow_mut()[report].write(scenario, item);
+            }
+            RowKey::ScenarioEnd { scenario } => {
+                let wall_time = *item
+                    .downcast::<f64>()
+                    .expect("Scenario end should carry the wall time");
+                self.finish_scenario(scenario, wall_time);
+            }
+        }
+    }
+
+    pub fn finish_scenario(&self, scenario: usize, wall_time: f64) {
+        self.wall_times.borrow_mut()[scenario] = Some(wall_time);
+        for sink in self.sinks.borrow_mut().iter_mut() {
+            sink.finish_scenario(scenario);
+        }
+    }
+
+    /// Finishes every sink and returns what was run
+    pub fn finish(self) -> Vec<ScenarioRun> {
+        let mut sinks = self.sinks.borrow_mut();
+        for sink in sinks.iter_mut() {
+            sink.finish();
+        }
+        let wall_times = self.wall_times.borrow();
+        self.scenarios
+            .iter()
+            .map(|info| ScenarioRun {
+                scenario: info.scenario,
+                random_seed: info.parameters.random_seed,
+                wall_time: wall_times[info.scenario],
+                row_counts: self
+                    .names
+                    .iter()
+                    .zip(sinks.iter())
+                    .map(|(name, sink)| (name.clone(), sink.get_row_count(info.scenario)))
+                    .collect(),
+            })
+            .collect()
+    }
+}
+
+/// Writes rows in scenario order whatever order scenarios run in. Rows of the earliest
+/// unfinished scenario are written as they arrive; rows of later scenarios are held until
+/// every earlier scenario has finished.
+pub struct ScenarioOrder {
+    next_scenario: usize,
+    pending: BTreeMap<usize, Vec<ReportRow>>,
+}
+
+impl ScenarioOrder {
+    pub fn new() -> Self {
+        ScenarioOrder {
+            next_scenario: 0,
+            pending: BTreeMap::new(),
+        }
+    }
+
+    pub fn write(&mut self, sinks: &ReportSinks, row: ReportRow) {
+        let (scenario, is_end) = match row.0 {
+            RowKey::Item { scenario, .. } => (scenario, false),
+            RowKey::ScenarioEnd { scenario } => (scenario, true),
+        };
+        if scenario != self.next_scenario {
+            self.pending.entry(scenario).or_default().push(row);
+            return;
+        }
+        sinks.write(row);
+        if is_end {
+            self.advance(sinks);
+        }
+    }
+
+    // Moves on to the next scenario, writing whatever it has already sent
+    fn advance(&mut self, sinks: &ReportSinks) {
+        loop {
+            self.next_scenario += 1;
+            let Some(rows) = self.pending.remove(&self.next_scenario) else {
+                return;
+            };
+            let mut finished = false;
+            for row in rows {
+                finished = matches!(row.0, RowKey::ScenarioEnd { .. });
+                sinks.write(row);
+            }
+            if !finished {
+                return;
+            }
+        }
+    }
+}
+
+impl Default for ScenarioOrder {
+    fn default() -> Self {
+        ScenarioOrder::new()
+    }
+}
+
+struct ReportRegistry<'a> {
+    sinks: &'a mut ReportSinks,
+    output: &'a ReportOutput,
+    config: &'a OutputConfig,
+    default_format: OutputFormat,
+    known_reports: BTreeSet<String>,
+}
+
+impl ReportRegistry<'_> {
+    /// Adds a sink for the report called `name` if `is_enabled` holds for the parameters of
+    /// any scenario, so reports that no scenario writes get no file or table
+    fn add<R, K>(
+        &mut self,
+        name: &str,
+        is_enabled: fn(&Parameters) -> bool,
+        get_key: fn(&ScenarioInfo) -> K,
+    ) where
+        R: Report + 'static,
+        R::Item: Serialize + DeserializeOwned + Send + 'static,
+        K: Serialize + ScenarioKey + Clone + 'static,
+    {
+        self.known_reports.insert(name.to_string());
+        if !self
+            .sinks
+            .scenarios
+            .iter()
+            .any(|info| is_enabled(&info.parameters))
+        {
+            return;
+        }
+        let format = self.config.get_format(name, self.default_format);
+        // Parquet and SQLite trace the key columns from the keys, as they vary by experiment
+        let keys = self.sinks.scenarios.iter().map(get_key).collect::<Vec<_>>();
+        let sink = self.output.create_sink::<K, R::Item>(name, format, &keys);
+        self.sinks.add::<R, K>(name, sink, get_key);
+    }
+}
+
+/// Adds a sink for every report that some scenario writes, in the format selected for it by
+/// the output config. New reports only need to be listed here.
+pub fn add_report_sinks(
+    sinks: &mut ReportSinks,
+    output: &ReportOutput,
+    config: &OutputConfig,
+    default_format: OutputFormat,
+) {
+    let mut registry = ReportRegistry {
+        sinks,
+        output,
+        config,
+        default_format,
+        known_reports: BTreeSet::new(),
+    };
+    registry.add::<IncidenceReport, Scenario>(
+        "incidence_report",
+        |parameters| parameters.report_aggregation.is_none(),
+        ScenarioInfo::get_key,
+    );
+    registry.add::<DeathReport, Scenario>(
+        "death_report",
+        |parameters| parameters.report_aggregation.is_none(),
+        ScenarioInfo::get_key,
+    );
+    registry.add::<TracingReport, Scenario>(
+        "tracing_report",
+        |parameters| parameters.contact_tracing.is_some(),
+        ScenarioInfo::get_key,
+    );
+    registry.add::<ObservedCaseReport, Scenario>(
+        "observed_case_report",
+        |parameters| parameters.testing.is_some(),
+        ScenarioInfo::get_key,
+    );
+    registry.add::<CompartmentReport, Scenario>(
+        "compartment_report",
+        |_| true,
+        ScenarioInfo::get_key,
+    );
+    registry.add::<IncidenceCountReport, Scenario>(
+        "incidence_counts",
+        |parameters| parameters.report_aggregation.is_some(),
+        ScenarioInfo::get_key,
+    );
+    registry.add::<DeathCountReport, Scenario>(
+        "death_counts",
+        |parameters| parameters.report_aggregation.is_some(),
+        ScenarioInfo::get_key,
+    );
+    registry.add::<LineListReport, Scenario>(
+        "line_list_report",
+        |parameters| parameters.line_list,
+        ScenarioInfo::get_key,
+    );
+    registry.add::<TransmissionTreeReport, Scenario>(
+        "transmission_tree_report",
+        |parameters| parameters.transmission_tree.is_some(),
+        ScenarioInfo::get_key,
+    );
+    registry.add::<NewickTreeReport, Scenario>(
+        "newick_trees",
+        |parameters| parameters.transmission_tree.is_some(),
+        ScenarioInfo::get_key,
+    );
+    registry.add::<SummaryReport, Scenario>("summary", |_| true, ScenarioInfo::get_parameter_key);
+    registry.add::<RtReport, Scenario>(
+        "rt_report",
+        |parameters| parameters.rt_interval.is_some(),
+        ScenarioInfo::get_key,
+    );
+
+    for report in config.reports.keys() {
+        assert!(
+            registry.known_reports.contains(report),
+            "Unknown report {} in output config; expected one of {:?}",
+            report,
+            registry.known_reports
+        );
+    }
+}
@@ -0,0 +1,166 @@
+use serde_derive::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Number, Value};
+
+use super::parameters::{Experiment, OutputConfig, Parameters};
+
+/// Base parameters and the values to sweep selected fields over. Fields are named by their
+/// dotted path, such as `isolation.contact_reduction`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Sweep {
+    #[serde(default)]
+    pub output: OutputConfig,
+    #[serde(default)]
+    pub replicates: Option<usize>,
+    #[serde(default)]
+    pub master_seed: Option<u64>,
+    pub base: Value,
+    /// Kept as a mapping so fields vary in the order they are listed
+    pub sweep: Mapping,
+}
+
+/// Either a list of values or an inclusive range
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SweepValues {
+    List(Vec<Value>),
+    Range(SweepRange),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SweepRange {
+    pub start: Number,
+    pub stop: Number,
+    pub step: Number,
+}
+
+impl SweepRange {
+    // Integer if every bound is, so that integer fields such as `population` can be swept
+    fn get_values(&self) -> Vec<Value> {
+        if let (Some(start), Some(stop), Some(step)) =
+            (self.start.as_i64(), self.stop.as_i64(), self.step.as_i64())
+        {
+            assert!(step > 0, "Sweep range step must be positive");
+            return (start..=stop)
+                .step_by(step as usize)
+                .map(Value::from)
+                .collect();
+        }
+        let start = self.start.as_f64().unwrap();
+        let stop = self.stop.as_f64().unwrap();
+        let step = self.step.as_f64().unwrap();
+        assert!(step > 0.0, "Sweep range step must be positive");
+        // Allow for rounding error so that `stop` itself is included
+        let steps = ((stop - start) / step + 1e-9).floor() as usize;
+        (0..=steps)
+            .map(|i| Value::from(((start + i as f64 * step) * 1e10).round() / 1e10))
+            .collect()
+    }
+}
+
+impl SweepValues {
+    fn get_values(&self) -> Vec<Value> {
+        match self {
+            SweepValues::List(values) => values.clone(),
+            SweepValues::Range(range) => range.get_values(),
+        }
+    }
+}
+
+// Sets the field at a dotted path, creating any missing blocks on the way
+pub fn set_parameter(parameters: &mut Value, path: &str, value: Value) {
+    let mut target = parameters;
+    for field in path.split('.') {
+        if target.is_null() {
+            *target = Value::Mapping(Mapping::new());
+        }
+        target = target
+            .as_mapping_mut()
+            .unwrap_or_else(|| panic!("Cannot sweep {}: {} is not in a block", path, field))
+            .entry(Value::from(field))
+            .or_insert(Value::Null);
+    }
+    *target = value;
+}
+
+impl Sweep {
+    /// Expands the sweep into the Cartesian product of its values, with the first field listed
+    /// varying slowest. Swept fields are written as columns in every report.
+    pub fn into_experiment(self) -> Experiment {
+        let mut parameter_columns = Vec::new();
+        let mut scenarios = vec![self.base];
+        for (field, values) in self.sweep {
+            let field = field
+                .as_str()
+                .expect("Swept fields should be named by strings")
+                .to_string();
+            let values = serde_yaml::from_value::<SweepValues>(values)
+                .unwrap_or_else(|e| panic!("Could not parse sweep values of {}: {}", field, e))
+                .get_values();
+            scenarios = scenarios
+                .iter()
+                .flat_map(|scenario| {
+                    values.iter().map(|value| {
+                        let mut scenario = scenario.clone();
+                        set_parameter(&mut scenario, &field, value.clone());
+                        scenario
+                    })
+                })
+                .collect();
+            parameter_columns.push(field);
+        }
+        let scenarios = scenarios
+            .into_iter()
+            .map(|scenario| {
+                serde_yaml::from_value::<Parameters>(scenario)
+                    .unwrap_or_else(|e| panic!("Invalid swept parameters: {}", e))
+            })
+            .collect();
+        Experiment {
+            output: self.output,
+            parameter_columns,
+            replicates: self.replicates,
+            master_seed: self.master_seed,
+            scenarios,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sweep_expands_to_cartesian_product() {
+        let sweep: Sweep = serde_yaml::from_str(
+            "base:
+  population: 1000
+  r0: 2.0
+  infectious_period: 3.0
+  initial_infections: 5
+  random_seed: 1
+  death_rate: 0.1
+sweep:
+  r0: [1.5, 2.5]
+  death_rate: {start: 0.1, stop: 0.3, step: 0.1}
+  population: {start: 1000, stop: 2000, step: 1000}",
+        )
+        .unwrap();
+        let experiment = sweep.into_experiment();
+        assert_eq!(
+            experiment.parameter_columns,
+            vec!["r0", "death_rate", "population"]
+        );
+        assert_eq!(experiment.scenarios.len(), 12);
+        let first = &experiment.scenarios[0];
+        let last = &experiment.scenarios[11];
+        assert_eq!(
+            (first.r0, first.death_rate, first.population),
+            (1.5, 0.1, 1000)
+        );
+        assert_eq!(
+            (last.r0, last.death_rate, last.population),
+            (2.5, 0.3, 2000)
+        );
+        assert_eq!(experiment.scenarios[2].death_rate, 0.2);
+    }
+}
@@ -0,0 +1,173 @@
+use std::collections::BTreeMap;
+
+use eosim::{
+    context::{Component, Context},
+    global_properties::GlobalPropertyContext,
+    people::PersonId,
+    person_properties::PersonPropertyContext,
+    reports::{Report, ReportsContext},
+};
+use serde_derive::{Deserialize, Serialize};
+
+use super::{
+    demography::get_age_group_label,
+    global_properties::ReportAggregation,
+    person_properties::{DiseaseStatus, IsolationStatus},
+};
+
+/// Person property used to stratify aggregated counts
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Stratification {
+    IsolationStatus,
+    AgeGroup,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct ReportAggregationParameters {
+    /// Width of the time bins
+    pub interval: f64,
+    #[serde(default)]
+    pub stratify_by: Option<Stratification>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct EventCount {
+    pub time: f64,
+    pub stratum: String,
+    pub count: usize,
+}
+
+pub struct IncidenceCountReport {}
+
+impl Report for IncidenceCountReport {
+    type Item = EventCount;
+}
+
+pub struct DeathCountReport {}
+
+impl Report for DeathCountReport {
+    type Item = EventCount;
+}
+
+#[derive(Clone, Copy)]
+enum CountedEvent {
+    Infection,
+    Death,
+}
+
+// Pending counts by time bin and stratum, released once each bin has closed
+pub struct AggregationState {
+    infections: BTreeMap<usize, BTreeMap<String, usize>>,
+    deaths: BTreeMap<usize, BTreeMap<String, usize>>,
+}
+
+impl AggregationState {
+    fn get_bins_mut(
+        &mut self,
+        event: CountedEvent,
+    ) -> &mut BTreeMap<usize, BTreeMap<String, usize>> {
+        match event {
+            CountedEvent::Infection => &mut self.infections,
+            CountedEvent::Death => &mut self.deaths,
+        }
+    }
+}
+
+eosim::define_plugin!(
+    AggregationPlugin,
+    AggregationState,
+    AggregationState {
+        infections: BTreeMap::new(),
+        deaths: BTreeMap::new(),
+    }
+);
+
+fn get_stratum(context: &mut Context, person_id: PersonId) -> String {
+    let stratify_by = context
+        .get_global_property_value::<ReportAggregation>()
+        .expect("Report aggregation not specified")
+        .stratify_by;
+    match stratify_by {
+        None => "all".to_string(),
+        Some(Stratification::IsolationStatus) => context
+            .get_person_property_value::<IsolationStatus>(person_id)
+            .label()
+            .to_string(),
+        Some(Stratification::AgeGroup) => get_age_group_label(context, person_id)
+            .expect("Stratifying by age group requires demography"),
+    }
+}
+
+fn count_event(context: &mut Context, person_id: PersonId, event: CountedEvent) {
+    let interval = context
+        .get_global_property_value::<ReportAggregation>()
+        .expect("Report aggregation not specified")
+        .interval;
+    let bin = (context.get_time() / interval).floor() as usize;
+    let stratum = get_stratum(context, person_id);
+    let bins = context
+        .get_data_container_mut::<AggregationPlugin>()
+        .get_bins_mut(event);
+    let new_bin = !bins.contains_key(&bin);
+    *bins.entry(bin).or_default().entry(stratum).or_insert(0) += 1;
+    if new_bin {
+        context.add_plan((bin + 1) as f64 * interval, move |context| {
+            release_bin(context, bin, event);
+        });
+    }
+}
+
+fn release_bin(context: &mut Context, bin: usize, event: CountedEvent) {
+    let interval = context
+        .get_global_property_value::<ReportAggregation>()
+        .expect("Report aggregation not specified")
+        .interval;
+    let counts = context
+        .get_data_container_mut::<AggregationPlugin>()
+        .get_bins_mut(event)
+        .remove(&bin)
+        .unwrap_or_default();
+    for (stratum, count) in counts {
+        let item = EventCount {
+            time: bin as f64 * interval,
+            stratum,
+            count,
+        };
+        match event {
+            CountedEvent::Infection => context.release_report_item::<IncidenceCountReport>(item),
+            CountedEvent::Death => context.release_report_item::<DeathCountReport>(item),
+        }
+    }
+}
+
+pub fn handle_person_disease_status_change(
+    context: &mut Context,
+    person_id: PersonId,
+    _: DiseaseStatus,
+) {
+    let disease_status = context.get_person_property_value::<DiseaseStatus>(person_id);
+    match disease_status {
+        DiseaseStatus::I => count_event(context, person_id, CountedEvent::Infection),
+        DiseaseStatus::D => count_event(context, person_id, CountedEvent::Death),
+        _ => {}
+    }
+}
+
+/// Counts infections and disease deaths in time bins in place of `IncidenceReport` and
+/// `DeathReport`; bins without events are not released
+pub struct AggregateReport {}
+
+impl Component for AggregateReport {
+    fn init(context: &mut Context) {
+        let aggregation = context
+            .get_global_property_value::<ReportAggregation>()
+            .expect("Report aggregation not specified");
+        assert!(
+            aggregation.interval > 0.0,
+            "Report aggregation interval must be positive"
+        );
+        context
+            .observe_person_property_changes::<DiseaseStatus>(handle_person_disease_status_change);
+    }
+}
@@ -0,0 +1,130 @@
+use std::collections::VecDeque;
+
+use eosim::{
+    context::{Component, Context},
+    global_properties::GlobalPropertyContext,
+    people::PersonId,
+    person_properties::PersonPropertyContext,
+};
+use serde_derive::{Deserialize, Serialize};
+
+use super::{
+    compartment_counts::get_compartment_counts,
+    global_properties::{Behavior, Population},
+    person_properties::DiseaseStatus,
+};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum BehaviorSignal {
+    /// Deaths per person per day
+    Deaths,
+    /// Fraction of the population currently infectious
+    Prevalence,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct BehaviorParameters {
+    pub signal: BehaviorSignal,
+    /// Number of days over which the signal is averaged
+    pub memory_window: usize,
+    /// Signal level at which contacts are reduced by half of `max_reduction`
+    pub half_saturation: f64,
+    /// Hill coefficient of the response curve
+    #[serde(default = "default_steepness")]
+    pub steepness: f64,
+    /// Largest fraction by which contacts can be reduced
+    pub max_reduction: f64,
+}
+
+fn default_steepness() -> f64 {
+    1.0
+}
+
+impl BehaviorParameters {
+    pub fn get_contact_multiplier(&self, signal: f64) -> f64 {
+        let response = signal.powf(self.steepness);
+        let half_saturation = self.half_saturation.powf(self.steepness);
+        1.0 - self.max_reduction * response / (response + half_saturation)
+    }
+}
+
+pub struct BehaviorState {
+    deaths_today: usize,
+    history: VecDeque<f64>,
+    contact_multiplier: f64,
+}
+
+eosim::define_plugin!(
+    BehaviorPlugin,
+    BehaviorState,
+    BehaviorState {
+        deaths_today: 0,
+        history: VecDeque::new(),
+        contact_multiplier: 1.0,
+    }
+);
+
+pub struct BehaviorManager {}
+
+impl Component for BehaviorManager {
+    fn init(context: &mut Context) {
+        let behavior = context
+            .get_global_property_value::<Behavior>()
+            .expect("Behavior parameters not specified");
+        assert!(
+            behavior.memory_window >= 1,
+            "Behavior memory window must be at least one day"
+        );
+        context
+            .observe_person_property_changes::<DiseaseStatus>(handle_person_disease_status_change);
+        context.add_plan(1.0, update_contact_multiplier);
+    }
+}
+
+pub fn handle_person_disease_status_change(
+    context: &mut Context,
+    person_id: PersonId,
+    _: DiseaseStatus,
+) {
+    let disease_status = context.get_person_property_value::<DiseaseStatus>(person_id);
+    if matches!(disease_status, DiseaseStatus::D) {
+        context
+            .get_data_container_mut::<BehaviorPlugin>()
+            .deaths_today += 1;
+    }
+}
+
+fn update_contact_multiplier(context: &mut Context) {
+    let behavior = *context
+        .get_global_property_value::<Behavior>()
+        .expect("Behavior parameters not specified");
+    let population = *context
+        .get_global_property_value::<Population>()
+        .expect("Population not specified") as f64;
+    let infectious = get_compartment_counts(context).infectious;
+    let state = context.get_data_container_mut::<BehaviorPlugin>();
+    let daily_signal = match behavior.signal {
+        BehaviorSignal::Deaths => state.deaths_today as f64 / population,
+        BehaviorSignal::Prevalence => infectious as f64 / population,
+    };
+    state.deaths_today = 0;
+    state.history.push_back(daily_signal);
+    while state.history.len() > behavior.memory_window {
+        state.history.pop_front();
+    }
+    let signal = state.history.iter().sum::<f64>() / state.history.len() as f64;
+    state.contact_multiplier = behavior.get_contact_multiplier(signal);
+
+    // Stop updating once the epidemic is over so the simulation can finish
+    if infectious > 0 {
+        let next_update_time = context.get_time() + 1.0;
+        context.add_plan(next_update_time, update_contact_multiplier);
+    }
+}
+
+pub fn get_contact_multiplier(context: &mut Context) -> f64 {
+    context
+        .get_data_container_mut::<BehaviorPlugin>()
+        .contact_multiplier
+}
@@ -0,0 +1,89 @@
+use eosim::{
+    context::{Component, Context},
+    global_properties::GlobalPropertyContext,
+    people::PersonId,
+    person_properties::PersonPropertyContext,
+};
+
+use super::{
+    global_properties::Population,
+    person_properties::{DiseaseStatus, VitalStatus},
+};
+
+/// Number of living people in each disease compartment, plus disease deaths
+#[derive(Clone, Copy, Default)]
+pub struct CompartmentCounts {
+    pub susceptible: usize,
+    pub infectious: usize,
+    pub recovered: usize,
+    pub dead: usize,
+}
+
+impl CompartmentCounts {
+    fn get_count_mut(&mut self, disease_status: DiseaseStatus) -> &mut usize {
+        match disease_status {
+            DiseaseStatus::S => &mut self.susceptible,
+            DiseaseStatus::I => &mut self.infectious,
+            DiseaseStatus::R => &mut self.recovered,
+            DiseaseStatus::D => &mut self.dead,
+        }
+    }
+}
+
+eosim::define_plugin!(
+    CompartmentCountsPlugin,
+    CompartmentCounts,
+    CompartmentCounts::default()
+);
+
+pub struct CompartmentCounter {}
+
+impl Component for CompartmentCounter {
+    fn init(context: &mut Context) {
+        let population = *context
+            .get_global_property_value::<Population>()
+            .expect("Population not specified");
+        context
+            .get_data_container_mut::<CompartmentCountsPlugin>()
+            .susceptible = population;
+        context
+            .observe_person_property_changes::<DiseaseStatus>(handle_person_disease_status_change);
+        context.observe_person_property_changes::<VitalStatus>(handle_person_vital_status_change);
+    }
+}
+
+pub fn handle_person_disease_status_change(
+    context: &mut Context,
+    person_id: PersonId,
+    previous_disease_status: DiseaseStatus,
+) {
+    let disease_status = context.get_person_property_value::<DiseaseStatus>(person_id);
+    let counts = context.get_data_container_mut::<CompartmentCountsPlugin>();
+    *counts.get_count_mut(previous_disease_status) -= 1;
+    *counts.get_count_mut(disease_status) += 1;
+}
+
+pub fn handle_person_vital_status_change(
+    context: &mut Context,
+    person_id: PersonId,
+    _: VitalStatus,
+) {
+    // People who die of other causes leave their compartment
+    let vital_status = context.get_person_property_value::<VitalStatus>(person_id);
+    if matches!(vital_status, VitalStatus::Dead) {
+        let disease_status = context.get_person_property_value::<DiseaseStatus>(person_id);
+        *context
+            .get_data_container_mut::<CompartmentCountsPlugin>()
+            .get_count_mut(disease_status) -= 1;
+    }
+}
+
+pub fn add_susceptible(context: &mut Context) {
+    context
+        .get_data_container_mut::<CompartmentCountsPlugin>()
+        .susceptible += 1;
+}
+
+pub fn get_compartment_counts(context: &mut Context) -> CompartmentCounts {
+    *context.get_data_container_mut::<CompartmentCountsPlugin>()
+}
@@ -0,0 +1,62 @@
+use eosim::{
+    context::{Component, Context},
+    global_properties::GlobalPropertyContext,
+    reports::{Report, ReportsContext},
+};
+use serde_derive::{Deserialize, Serialize};
+
+use super::{
+    compartment_counts::get_compartment_counts, global_properties::CompartmentReportInterval,
+};
+
+pub struct CompartmentReport {}
+
+#[derive(Serialize, Deserialize)]
+pub struct Compartments {
+    pub time: f64,
+    #[serde(rename = "S")]
+    pub susceptible: usize,
+    #[serde(rename = "I")]
+    pub infectious: usize,
+    #[serde(rename = "R")]
+    pub recovered: usize,
+    #[serde(rename = "D")]
+    pub dead: usize,
+}
+
+impl Report for CompartmentReport {
+    type Item = Compartments;
+}
+
+fn release_compartment_counts(context: &mut Context) {
+    let counts = get_compartment_counts(context);
+    context.release_report_item::<CompartmentReport>(Compartments {
+        time: context.get_time(),
+        susceptible: counts.susceptible,
+        infectious: counts.infectious,
+        recovered: counts.recovered,
+        dead: counts.dead,
+    });
+
+    // Stop reporting once the epidemic is over so the simulation can finish
+    if counts.infectious > 0 {
+        let interval = *context
+            .get_global_property_value::<CompartmentReportInterval>()
+            .expect("Compartment report interval not specified");
+        let next_report_time = context.get_time() + interval;
+        context.add_plan(next_report_time, release_compartment_counts);
+    }
+}
+
+impl Component for CompartmentReport {
+    fn init(context: &mut Context) {
+        let interval = *context
+            .get_global_property_value::<CompartmentReportInterval>()
+            .expect("Compartment report interval not specified");
+        assert!(
+            interval > 0.0,
+            "Compartment report interval must be positive"
+        );
+        context.add_plan(0.0, release_compartment_counts);
+    }
+}
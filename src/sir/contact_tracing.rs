@@ -0,0 +1,153 @@
+use std::collections::HashSet;
+
+use eosim::{
+    context::{Component, Context},
+    global_properties::GlobalPropertyContext,
+    people::PersonId,
+    person_properties::PersonPropertyContext,
+    random::RandomContext,
+    reports::ReportsContext,
+};
+use rand::Rng;
+use rand_distr::{Distribution, Exp};
+use serde_derive::{Deserialize, Serialize};
+
+use super::{
+    global_properties::{ContactTracing, Isolation},
+    isolation_manager::quarantine,
+    person_properties::{DiseaseStatus, IsolationStatus},
+    tracing_report::{TracingAttempt, TracingReport},
+    transmission_manager::get_contacts,
+};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct ContactTracingParameters {
+    /// Probability that a contact within capacity is successfully traced
+    pub success_probability: f64,
+    /// Mean time from an index case being isolated to a contact being reached
+    pub mean_delay: f64,
+    /// Maximum number of contacts that tracers can attempt per day
+    pub daily_capacity: usize,
+}
+
+pub struct ContactTracingManager {}
+
+impl Component for ContactTracingManager {
+    fn init(context: &mut Context) {
+        context.observe_person_property_changes::<IsolationStatus>(
+            handle_person_isolation_status_change,
+        );
+    }
+}
+
+pub struct TracingCapacity {
+    day: usize,
+    attempts: usize,
+}
+
+eosim::define_plugin!(
+    TracingCapacityPlugin,
+    TracingCapacity,
+    TracingCapacity {
+        day: 0,
+        attempts: 0
+    }
+);
+
+eosim::define_random_id!(ContactTracingRandomId);
+
+pub fn handle_person_isolation_status_change(
+    context: &mut Context,
+    person_id: PersonId,
+    _: IsolationStatus,
+) {
+    // Every isolated case, whether detected or traced, becomes an index case
+    let isolation_status = context.get_person_property_value::<IsolationStatus>(person_id);
+    if matches!(isolation_status, IsolationStatus::Isolated) {
+        // Repeat contacts with the same person are only traced once
+        let mut contact_ids = HashSet::new();
+        for contact_id in get_contacts(context, person_id) {
+            if contact_ids.insert(contact_id) {
+                attempt_trace(context, person_id, contact_id);
+            }
+        }
+    }
+}
+
+fn attempt_trace(context: &mut Context, index_case_id: PersonId, contact_id: PersonId) {
+    let contact_tracing = *context
+        .get_global_property_value::<ContactTracing>()
+        .expect("Contact tracing parameters not specified");
+    let time = context.get_time();
+
+    // Tracer capacity is reset at the start of each day
+    let capacity = context.get_data_container_mut::<TracingCapacityPlugin>();
+    let day = time.floor() as usize;
+    if capacity.day != day {
+        capacity.day = day;
+        capacity.attempts = 0;
+    }
+    if capacity.attempts >= contact_tracing.daily_capacity {
+        release_tracing_attempt(context, index_case_id, contact_id, "missed_capacity", None);
+        return;
+    }
+    capacity.attempts += 1;
+
+    let mut rng = context.get_rng::<ContactTracingRandomId>();
+    let traced = rng.gen::<f64>() < contact_tracing.success_probability;
+    let delay = Exp::new(1.0 / contact_tracing.mean_delay)
+        .unwrap()
+        .sample(&mut *rng);
+    drop(rng);
+    if traced {
+        context.add_plan(time + delay, move |context| {
+            trace_contact(context, index_case_id, contact_id, delay);
+        });
+    } else {
+        release_tracing_attempt(context, index_case_id, contact_id, "missed", None);
+    }
+}
+
+fn trace_contact(context: &mut Context, index_case_id: PersonId, contact_id: PersonId, delay: f64) {
+    release_tracing_attempt(context, index_case_id, contact_id, "traced", Some(delay));
+    let disease_status = context.get_person_property_value::<DiseaseStatus>(contact_id);
+    let isolation_status = context.get_person_property_value::<IsolationStatus>(contact_id);
+    match (disease_status, isolation_status) {
+        (_, IsolationStatus::Isolated) => {}
+        // Traced infected contacts are isolated without waiting to be detected
+        (DiseaseStatus::I, _) => {
+            context.set_person_property_value::<IsolationStatus>(
+                contact_id,
+                IsolationStatus::Isolated,
+            );
+        }
+        (DiseaseStatus::S, _) => {
+            let quarantine_contacts = context
+                .get_global_property_value::<Isolation>()
+                .expect("Isolation parameters not specified")
+                .quarantine_contacts;
+            if quarantine_contacts {
+                quarantine(context, contact_id);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn release_tracing_attempt(
+    context: &mut Context,
+    index_case_id: PersonId,
+    contact_id: PersonId,
+    outcome: &str,
+    delay: Option<f64>,
+) {
+    let disease_status = context.get_person_property_value::<DiseaseStatus>(contact_id);
+    context.release_report_item::<TracingReport>(TracingAttempt {
+        time: context.get_time(),
+        index_case_id: index_case_id.id(),
+        contact_id: contact_id.id(),
+        outcome: outcome.to_string(),
+        infected: !matches!(disease_status, DiseaseStatus::S),
+        delay,
+    })
+}
@@ -4,15 +4,16 @@ use eosim::{
     person_properties::PersonPropertyContext,
     reports::{Report, ReportsContext},
 };
-use serde_derive::Serialize;
+use serde_derive::{Deserialize, Serialize};
 
 use super::person_properties::DiseaseStatus;
 
 pub struct DeathReport {}
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize)]
 pub struct Death {
     pub time: f64,
+    pub person_id: usize,
 }
 
 impl Report for DeathReport {
@@ -28,6 +29,7 @@ pub fn handle_person_death(
     if matches!(disease_status, DiseaseStatus::D) {
         context.release_report_item::<DeathReport>(Death {
             time: context.get_time(),
+            person_id: person_id.id(),
         })
     }
 }
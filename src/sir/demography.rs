@@ -0,0 +1,315 @@
+use eosim::{
+    context::{Component, Context},
+    global_properties::GlobalPropertyContext,
+    people::{PeopleContext, PersonId},
+    person_properties::PersonPropertyContext,
+    random::RandomContext,
+};
+use fxhash::FxHashMap;
+use rand::{distributions::WeightedIndex, Rng};
+use rand_distr::{Distribution, Exp};
+use serde_derive::{Deserialize, Serialize};
+
+use super::{
+    compartment_counts::add_susceptible,
+    global_properties::{Demography, Population},
+    person_properties::{DiseaseStatus, VitalStatus},
+    transmission_manager::add_contactable_person,
+};
+
+const DAYS_PER_YEAR: f64 = 365.0;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AgeGroupParameters {
+    /// Lower bound of the age group in years
+    pub min_age: f64,
+    /// Fraction of the initial population in the age group
+    pub initial_fraction: f64,
+    /// Background mortality rate per person per year
+    pub mortality_rate: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DemographyParameters {
+    /// Births per living person per year
+    pub birth_rate: f64,
+    /// Age groups in increasing order of `min_age`
+    pub age_groups: Vec<AgeGroupParameters>,
+    /// Upper bound of the initial ages in years
+    pub max_age: f64,
+    /// Days between aging steps, at which mortality rates are updated
+    #[serde(default = "default_aging_interval")]
+    pub aging_interval: f64,
+    /// Days after which births and aging stop
+    pub duration: f64,
+}
+
+fn default_aging_interval() -> f64 {
+    DAYS_PER_YEAR
+}
+
+impl DemographyParameters {
+    /// Index of the age group containing `age`, assuming the groups are sorted
+    pub fn get_age_group(&self, age: f64) -> usize {
+        self.age_groups
+            .iter()
+            .rposition(|age_group| age_group.min_age <= age)
+            .unwrap_or(0)
+    }
+
+    fn get_age_group_max_age(&self, age_group: usize) -> f64 {
+        self.age_groups
+            .get(age_group + 1)
+            .map_or(self.max_age, |next_age_group| next_age_group.min_age)
+    }
+
+    pub fn get_age_group_label(&self, age_group: usize) -> String {
+        match self.age_groups.get(age_group + 1) {
+            Some(next_age_group) => format!(
+                "{}-{}",
+                self.age_groups[age_group].min_age, next_age_group.min_age
+            ),
+            None => format!("{}+", self.age_groups[age_group].min_age),
+        }
+    }
+}
+
+pub struct DemographyState {
+    // Deterministic hashing keeps iteration order, and so random draws, reproducible
+    birth_times: FxHashMap<PersonId, f64>,
+    births: usize,
+    living: usize,
+}
+
+eosim::define_plugin!(
+    DemographyPlugin,
+    DemographyState,
+    DemographyState {
+        birth_times: FxHashMap::default(),
+        births: 0,
+        living: 0,
+    }
+);
+
+eosim::define_random_id!(DemographyRandomId);
+
+pub struct DemographyManager {}
+
+impl Component for DemographyManager {
+    fn init(context: &mut Context) {
+        let population = *context
+            .get_global_property_value::<Population>()
+            .expect("Population not specified");
+        let demography = context
+            .get_global_property_value::<Demography>()
+            .expect("Demography parameters not specified")
+            .clone();
+
+        // Assign initial ages by sampling an age group and then a uniform age within it
+        let age_group_dist = WeightedIndex::new(
+            demography
+                .age_groups
+                .iter()
+                .map(|age_group| age_group.initial_fraction),
+        )
+        .expect("Invalid initial age distribution");
+        let mut rng = context.get_rng::<DemographyRandomId>();
+        let birth_times = (0..population)
+            .map(|id| {
+                let age_group = age_group_dist.sample(&mut *rng);
+                let min_age = demography.age_groups[age_group].min_age;
+                let max_age = demography.get_age_group_max_age(age_group);
+                let age = min_age + rng.gen::<f64>() * (max_age - min_age);
+                (PersonId::new(id), -age * DAYS_PER_YEAR)
+            })
+            .collect::<Vec<_>>();
+        drop(rng);
+        let state = context.get_data_container_mut::<DemographyPlugin>();
+        state.birth_times.extend(birth_times);
+        state.living = population;
+
+        context
+            .observe_person_property_changes::<DiseaseStatus>(handle_person_disease_status_change);
+        age_population(context);
+        schedule_next_birth(context);
+    }
+}
+
+pub fn handle_person_disease_status_change(
+    context: &mut Context,
+    person_id: PersonId,
+    previous_disease_status: DiseaseStatus,
+) {
+    // Observers of the infection may already have set D, so only the change from I counts
+    let disease_status = context.get_person_property_value::<DiseaseStatus>(person_id);
+    if matches!(previous_disease_status, DiseaseStatus::I)
+        && matches!(disease_status, DiseaseStatus::D)
+    {
+        context.get_data_container_mut::<DemographyPlugin>().living -= 1;
+    }
+}
+
+pub fn get_age(context: &mut Context, person_id: PersonId) -> Option<f64> {
+    let time = context.get_time();
+    context
+        .get_data_container_mut::<DemographyPlugin>()
+        .birth_times
+        .get(&person_id)
+        .map(|birth_time| (time - birth_time) / DAYS_PER_YEAR)
+}
+
+pub fn get_age_group_label(context: &mut Context, person_id: PersonId) -> Option<String> {
+    let age = get_age(context, person_id)?;
+    let demography = context.get_global_property_value::<Demography>()?;
+    Some(demography.get_age_group_label(demography.get_age_group(age)))
+}
+
+/// Number of people born during the run, which is zero without demography
+pub fn get_births(context: &mut Context) -> usize {
+    context.get_data_container_mut::<DemographyPlugin>().births
+}
+
+/// Draws background deaths for the living population over the next aging interval
+fn age_population(context: &mut Context) {
+    let demography = context
+        .get_global_property_value::<Demography>()
+        .expect("Demography parameters not specified")
+        .clone();
+    let time = context.get_time();
+    let ages = context
+        .get_data_container_mut::<DemographyPlugin>()
+        .birth_times
+        .iter()
+        .map(|(person_id, birth_time)| (*person_id, (time - birth_time) / DAYS_PER_YEAR))
+        .collect::<Vec<_>>();
+    for (person_id, age) in ages {
+        // The dead are kept until the next aging step so reports can look up their age at death
+        if !is_living(context, person_id) {
+            context
+                .get_data_container_mut::<DemographyPlugin>()
+                .birth_times
+                .remove(&person_id);
+            continue;
+        }
+        let mortality_rate =
+            demography.age_groups[demography.get_age_group(age)].mortality_rate / DAYS_PER_YEAR;
+        if mortality_rate <= 0.0 {
+            continue;
+        }
+        let time_to_death = Exp::new(mortality_rate)
+            .unwrap()
+            .sample(&mut *context.get_rng::<DemographyRandomId>());
+        if time_to_death < demography.aging_interval {
+            context.add_plan(time + time_to_death, move |context| {
+                kill_person(context, person_id);
+            });
+        }
+    }
+
+    let next_aging_time = time + demography.aging_interval;
+    if next_aging_time < demography.duration {
+        context.add_plan(next_aging_time, age_population);
+    }
+}
+
+fn is_living(context: &mut Context, person_id: PersonId) -> bool {
+    let vital_status = context.get_person_property_value::<VitalStatus>(person_id);
+    let disease_status = context.get_person_property_value::<DiseaseStatus>(person_id);
+    matches!(vital_status, VitalStatus::Alive) && !matches!(disease_status, DiseaseStatus::D)
+}
+
+fn kill_person(context: &mut Context, person_id: PersonId) {
+    if is_living(context, person_id) {
+        context.get_data_container_mut::<DemographyPlugin>().living -= 1;
+        context.set_person_property_value::<VitalStatus>(person_id, VitalStatus::Dead);
+    }
+}
+
+fn schedule_next_birth(context: &mut Context) {
+    let demography = context
+        .get_global_property_value::<Demography>()
+        .expect("Demography parameters not specified")
+        .clone();
+    let living = context.get_data_container_mut::<DemographyPlugin>().living;
+    let birth_rate = demography.birth_rate / DAYS_PER_YEAR * living as f64;
+    if birth_rate <= 0.0 {
+        return;
+    }
+    let next_birth_time = context.get_time()
+        + Exp::new(birth_rate)
+            .unwrap()
+            .sample(&mut *context.get_rng::<DemographyRandomId>());
+    if next_birth_time < demography.duration {
+        context.add_plan(next_birth_time, |context| {
+            add_newborn(context);
+            schedule_next_birth(context);
+        });
+    }
+}
+
+/// Adds a susceptible newborn, whose background mortality starts at the next aging step
+fn add_newborn(context: &mut Context) {
+    let person_id = context.add_person().execute();
+    let time = context.get_time();
+    let state = context.get_data_container_mut::<DemographyPlugin>();
+    state.birth_times.insert(person_id, time);
+    state.births += 1;
+    state.living += 1;
+    add_susceptible(context);
+    add_contactable_person(context, person_id);
+}
+
+#[cfg(test)]
+mod tests {
+    use eosim::{context::Context, global_properties::GlobalPropertyContext, people::PersonId};
+
+    use super::*;
+    use crate::sir::{
+        death_manager::DeathManager,
+        global_properties::{DeathRate, InfectiousPeriod, InitialInfections, R0},
+        infection_manager::InfectionManager,
+        infection_seeder::InfectionSeeder,
+        population_loader::PopulationLoader,
+        transmission_manager::TransmissionManager,
+    };
+
+    #[test]
+    fn test_living_count_matches_people_alive() {
+        let population = 1000;
+        let mut context = Context::new();
+        context.set_global_property_value::<Population>(population);
+        context.set_global_property_value::<R0>(5.0);
+        context.set_global_property_value::<InfectiousPeriod>(4.0);
+        context.set_global_property_value::<InitialInfections>(50);
+        context.set_global_property_value::<DeathRate>(0.9);
+        context.set_global_property_value::<Demography>(DemographyParameters {
+            birth_rate: 0.5,
+            age_groups: vec![AgeGroupParameters {
+                min_age: 0.0,
+                initial_fraction: 1.0,
+                mortality_rate: 0.5,
+            }],
+            max_age: 80.0,
+            aging_interval: 30.0,
+            duration: 365.0,
+        });
+        context.set_base_random_seed(8675309);
+        context.add_component::<PopulationLoader>();
+        context.add_component::<InfectionManager>();
+        context.add_component::<TransmissionManager>();
+        context.add_component::<InfectionSeeder>();
+        context.add_component::<DeathManager>();
+        context.add_component::<DemographyManager>();
+        context.execute();
+
+        let people = population + get_births(&mut context);
+        let alive = (0..people)
+            .filter(|id| is_living(&mut context, PersonId::new(*id)))
+            .count();
+        assert!(alive < people, "Expected some deaths");
+        assert_eq!(
+            context.get_data_container_mut::<DemographyPlugin>().living,
+            alive
+        );
+    }
+}
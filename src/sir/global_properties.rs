@@ -1,3 +1,9 @@
+use super::{
+    aggregate_report::ReportAggregationParameters, behavior_manager::BehaviorParameters,
+    contact_tracing::ContactTracingParameters, demography::DemographyParameters,
+    isolation_manager::IsolationParameters, testing_manager::TestingParameters,
+};
+
 eosim::define_global_property!(R0, f64);
 
 eosim::define_global_property!(InfectiousPeriod, f64);
@@ -7,3 +13,19 @@ eosim::define_global_property!(Population, usize);
 eosim::define_global_property!(InitialInfections, usize);
 
 eosim::define_global_property!(DeathRate, f64);
+
+eosim::define_global_property!(Isolation, IsolationParameters);
+
+eosim::define_global_property!(ContactTracing, ContactTracingParameters);
+
+eosim::define_global_property!(Testing, TestingParameters);
+
+eosim::define_global_property!(Behavior, BehaviorParameters);
+
+eosim::define_global_property!(Demography, DemographyParameters);
+
+eosim::define_global_property!(CompartmentReportInterval, f64);
+
+eosim::define_global_property!(ReportAggregation, ReportAggregationParameters);
+
+eosim::define_global_property!(RtInterval, f64);
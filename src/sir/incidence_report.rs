@@ -4,15 +4,16 @@ use eosim::{
     person_properties::PersonPropertyContext,
     reports::{Report, ReportsContext},
 };
-use serde_derive::Serialize;
+use serde_derive::{Deserialize, Serialize};
 
 use super::person_properties::DiseaseStatus;
 
 pub struct IncidenceReport {}
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize)]
 pub struct Infection {
     pub time: f64,
+    pub person_id: usize,
 }
 
 impl Report for IncidenceReport {
@@ -28,6 +29,7 @@ pub fn handle_person_disease_status_change(
     if matches!(disease_status, DiseaseStatus::I) {
         context.release_report_item::<IncidenceReport>(Infection {
             time: context.get_time(),
+            person_id: person_id.id(),
         })
     }
 }
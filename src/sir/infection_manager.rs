@@ -7,7 +7,10 @@ use eosim::{
 };
 use rand_distr::{Distribution, Exp};
 
-use super::{global_properties::InfectiousPeriod, person_properties::DiseaseStatus};
+use super::{
+    global_properties::InfectiousPeriod,
+    person_properties::{DiseaseStatus, VitalStatus},
+};
 
 pub struct InfectionManager {}
 
@@ -32,7 +35,11 @@ pub fn schedule_recovery(context: &mut Context, person_id: PersonId) {
     let recovery_time = context.get_time()
         + infectious_period_dist.sample(&mut *context.get_rng::<InfectionRandomId>());
     context.add_plan(recovery_time, move |context| {
-        context.set_person_property_value::<DiseaseStatus>(person_id, DiseaseStatus::R);
+        // People who died of other causes while infectious do not recover
+        let vital_status = context.get_person_property_value::<VitalStatus>(person_id);
+        if matches!(vital_status, VitalStatus::Alive) {
+            context.set_person_property_value::<DiseaseStatus>(person_id, DiseaseStatus::R);
+        }
     });
 }
 
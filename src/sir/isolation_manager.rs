@@ -0,0 +1,160 @@
+use eosim::{
+    context::{Component, Context},
+    global_properties::GlobalPropertyContext,
+    people::PersonId,
+    person_properties::PersonPropertyContext,
+    random::RandomContext,
+};
+use rand::Rng;
+use serde_derive::{Deserialize, Serialize};
+
+use super::{
+    global_properties::{ContactTracing, Isolation},
+    person_properties::{DiseaseStatus, IsolationStatus},
+    transmission_manager::get_contacts,
+};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct IsolationParameters {
+    /// Probability that an infected person is detected as a case
+    pub detection_probability: f64,
+    /// Time from infection to detection
+    pub detection_delay: f64,
+    /// Fraction by which isolated and quarantined people reduce their contact rate
+    pub contact_reduction: f64,
+    /// Whether the recorded contacts of a detected case are quarantined
+    #[serde(default)]
+    pub quarantine_contacts: bool,
+    /// Time after which quarantined people are released
+    #[serde(default = "default_quarantine_duration")]
+    pub quarantine_duration: f64,
+}
+
+fn default_quarantine_duration() -> f64 {
+    14.0
+}
+
+pub struct IsolationManager {}
+
+impl Component for IsolationManager {
+    fn init(context: &mut Context) {
+        context
+            .observe_person_property_changes::<DiseaseStatus>(handle_person_disease_status_change);
+    }
+}
+
+eosim::define_random_id!(IsolationRandomId);
+
+pub fn handle_person_disease_status_change(
+    context: &mut Context,
+    person_id: PersonId,
+    _: DiseaseStatus,
+) {
+    let disease_status = context.get_person_property_value::<DiseaseStatus>(person_id);
+    if matches!(disease_status, DiseaseStatus::I) {
+        schedule_detection(context, person_id);
+    }
+}
+
+pub fn schedule_detection(context: &mut Context, person_id: PersonId) {
+    let isolation = *context
+        .get_global_property_value::<Isolation>()
+        .expect("Isolation parameters not specified");
+    let mut rng = context.get_rng::<IsolationRandomId>();
+    let detected = rng.gen::<f64>() < isolation.detection_probability;
+    drop(rng);
+    if detected {
+        let detection_time = context.get_time() + isolation.detection_delay;
+        context.add_plan(detection_time, move |context| {
+            detect_case(context, person_id);
+        });
+    }
+}
+
+fn detect_case(context: &mut Context, person_id: PersonId) {
+    // Cases that have recovered, died or were already isolated by tracing are skipped
+    let disease_status = context.get_person_property_value::<DiseaseStatus>(person_id);
+    let isolation_status = context.get_person_property_value::<IsolationStatus>(person_id);
+    if !matches!(disease_status, DiseaseStatus::I)
+        || matches!(isolation_status, IsolationStatus::Isolated)
+    {
+        return;
+    }
+    context.set_person_property_value::<IsolationStatus>(person_id, IsolationStatus::Isolated);
+
+    // With contact tracing enabled, contacts are only quarantined once they are traced
+    let quarantine_contacts = context
+        .get_global_property_value::<Isolation>()
+        .expect("Isolation parameters not specified")
+        .quarantine_contacts;
+    let contact_tracing = context
+        .get_global_property_value::<ContactTracing>()
+        .is_some();
+    if quarantine_contacts && !contact_tracing {
+        for contact_id in get_contacts(context, person_id) {
+            quarantine(context, contact_id);
+        }
+    }
+}
+
+pub fn quarantine(context: &mut Context, person_id: PersonId) {
+    let isolation_status = context.get_person_property_value::<IsolationStatus>(person_id);
+    if !matches!(isolation_status, IsolationStatus::Free) {
+        return;
+    }
+    context.set_person_property_value::<IsolationStatus>(person_id, IsolationStatus::Quarantined);
+    let quarantine_duration = context
+        .get_global_property_value::<Isolation>()
+        .expect("Isolation parameters not specified")
+        .quarantine_duration;
+    let release_time = context.get_time() + quarantine_duration;
+    context.add_plan(release_time, move |context| {
+        release_quarantine(context, person_id);
+    });
+}
+
+fn release_quarantine(context: &mut Context, person_id: PersonId) {
+    // People isolated during their quarantine stay isolated
+    let isolation_status = context.get_person_property_value::<IsolationStatus>(person_id);
+    if matches!(isolation_status, IsolationStatus::Quarantined) {
+        context.set_person_property_value::<IsolationStatus>(person_id, IsolationStatus::Free);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use eosim::{
+        context::Context, global_properties::GlobalPropertyContext, people::PersonId,
+        person_properties::PersonPropertyContext,
+    };
+
+    use super::*;
+    use crate::sir::{global_properties::Population, population_loader::PopulationLoader};
+
+    #[test]
+    fn test_quarantine_ends_after_duration() {
+        let mut context = Context::new();
+        context.set_global_property_value::<Population>(10);
+        context.set_global_property_value::<Isolation>(IsolationParameters {
+            detection_probability: 0.0,
+            detection_delay: 0.0,
+            contact_reduction: 1.0,
+            quarantine_contacts: true,
+            quarantine_duration: 7.0,
+        });
+        context.add_component::<PopulationLoader>();
+
+        let person_id = PersonId::new(0);
+        quarantine(&mut context, person_id);
+        assert!(matches!(
+            context.get_person_property_value::<IsolationStatus>(person_id),
+            IsolationStatus::Quarantined
+        ));
+        context.execute();
+        assert_eq!(context.get_time(), 7.0);
+        assert!(matches!(
+            context.get_person_property_value::<IsolationStatus>(person_id),
+            IsolationStatus::Free
+        ));
+    }
+}
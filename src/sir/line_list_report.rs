@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+
+use eosim::{
+    context::{Component, Context},
+    people::PersonId,
+    person_properties::PersonPropertyContext,
+    reports::{Report, ReportsContext},
+};
+use serde_derive::{Deserialize, Serialize};
+
+use super::{
+    demography::get_age_group_label,
+    person_properties::{DiseaseStatus, IsolationStatus, VitalStatus},
+    transmission_manager::get_infector,
+};
+
+pub struct LineListReport {}
+
+#[derive(Serialize, Deserialize)]
+pub struct LineListEntry {
+    pub person_id: usize,
+    pub infection_time: f64,
+    pub infector_id: Option<usize>,
+    pub outcome_time: f64,
+    pub outcome: String,
+    pub age_group: Option<String>,
+    pub isolation_status: String,
+}
+
+impl Report for LineListReport {
+    type Item = LineListEntry;
+}
+
+pub struct PendingInfection {
+    time: f64,
+    infector_id: Option<PersonId>,
+    age_group: Option<String>,
+}
+
+// Infections that have not yet reached an outcome
+eosim::define_plugin!(
+    LineListPlugin,
+    HashMap<PersonId, PendingInfection>,
+    HashMap::new()
+);
+
+pub fn handle_person_disease_status_change(
+    context: &mut Context,
+    person_id: PersonId,
+    _: DiseaseStatus,
+) {
+    let disease_status = context.get_person_property_value::<DiseaseStatus>(person_id);
+    match disease_status {
+        DiseaseStatus::I => {
+            let infection = PendingInfection {
+                time: context.get_time(),
+                infector_id: get_infector(context, person_id),
+                age_group: get_age_group_label(context, person_id),
+            };
+            context
+                .get_data_container_mut::<LineListPlugin>()
+                .insert(person_id, infection);
+        }
+        DiseaseStatus::R => release_entry(context, person_id, "recovered"),
+        DiseaseStatus::D => release_entry(context, person_id, "died"),
+        DiseaseStatus::S => {}
+    }
+}
+
+pub fn handle_person_vital_status_change(
+    context: &mut Context,
+    person_id: PersonId,
+    _: VitalStatus,
+) {
+    let vital_status = context.get_person_property_value::<VitalStatus>(person_id);
+    if matches!(vital_status, VitalStatus::Dead) {
+        release_entry(context, person_id, "died_other_cause");
+    }
+}
+
+fn release_entry(context: &mut Context, person_id: PersonId, outcome: &str) {
+    let infection = context
+        .get_data_container_mut::<LineListPlugin>()
+        .remove(&person_id);
+    if let Some(infection) = infection {
+        let isolation_status = context.get_person_property_value::<IsolationStatus>(person_id);
+        context.release_report_item::<LineListReport>(LineListEntry {
+            person_id: person_id.id(),
+            infection_time: infection.time,
+            infector_id: infection.infector_id.map(|infector_id| infector_id.id()),
+            outcome_time: context.get_time(),
+            outcome: outcome.to_string(),
+            age_group: infection.age_group,
+            isolation_status: isolation_status.label().to_string(),
+        })
+    }
+}
+
+impl Component for LineListReport {
+    fn init(context: &mut Context) {
+        context
+            .observe_person_property_changes::<DiseaseStatus>(handle_person_disease_status_change);
+        context.observe_person_property_changes::<VitalStatus>(handle_person_vital_status_change);
+    }
+}
@@ -7,3 +7,17 @@ pub mod population_loader;
 pub mod transmission_manager;
 pub mod death_manager;
 pub mod death_report;
+pub mod isolation_manager;
+pub mod contact_tracing;
+pub mod tracing_report;
+pub mod testing_manager;
+pub mod observed_case_report;
+pub mod behavior_manager;
+pub mod demography;
+pub mod compartment_counts;
+pub mod compartment_report;
+pub mod aggregate_report;
+pub mod line_list_report;
+pub mod transmission_tree_report;
+pub mod summary_report;
+pub mod rt_report;
@@ -0,0 +1,14 @@
+use eosim::reports::Report;
+use serde_derive::{Deserialize, Serialize};
+
+pub struct ObservedCaseReport {}
+
+#[derive(Serialize, Deserialize)]
+pub struct ObservedCase {
+    pub time: f64,
+    pub infection_time: f64,
+}
+
+impl Report for ObservedCaseReport {
+    type Item = ObservedCase;
+}
@@ -5,3 +5,26 @@ pub enum DiseaseStatus {
     D,
 }
 eosim::define_person_property_from_enum!(DiseaseStatus, DiseaseStatus::S);
+
+pub enum IsolationStatus {
+    Free,
+    Isolated,
+    Quarantined,
+}
+eosim::define_person_property_from_enum!(IsolationStatus, IsolationStatus::Free);
+
+pub enum VitalStatus {
+    Alive,
+    Dead,
+}
+eosim::define_person_property_from_enum!(VitalStatus, VitalStatus::Alive);
+
+impl IsolationStatus {
+    pub fn label(&self) -> &'static str {
+        match self {
+            IsolationStatus::Free => "free",
+            IsolationStatus::Isolated => "isolated",
+            IsolationStatus::Quarantined => "quarantined",
+        }
+    }
+}
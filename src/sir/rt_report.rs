@@ -0,0 +1,100 @@
+use std::collections::{BTreeMap, HashMap};
+
+use eosim::{
+    context::{Component, Context},
+    global_properties::GlobalPropertyContext,
+    people::PersonId,
+    person_properties::PersonPropertyContext,
+    reports::{Report, ReportsContext},
+};
+use serde_derive::{Deserialize, Serialize};
+
+use super::{
+    global_properties::RtInterval, person_properties::DiseaseStatus,
+    transmission_manager::get_infector,
+};
+
+pub struct RtReport {}
+
+#[derive(Serialize, Deserialize)]
+pub struct CaseReproductionNumber {
+    pub time: f64,
+    pub infectors: usize,
+    pub secondary_infections: usize,
+    pub rt: f64,
+}
+
+impl Report for RtReport {
+    type Item = CaseReproductionNumber;
+}
+
+#[derive(Default)]
+pub struct BinCounts {
+    infectors: usize,
+    secondary_infections: usize,
+}
+
+#[derive(Default)]
+pub struct RtState {
+    infection_bins: HashMap<PersonId, usize>,
+    bins: BTreeMap<usize, BinCounts>,
+}
+
+eosim::define_plugin!(RtPlugin, RtState, RtState::default());
+
+pub fn handle_person_disease_status_change(
+    context: &mut Context,
+    person_id: PersonId,
+    _: DiseaseStatus,
+) {
+    let disease_status = context.get_person_property_value::<DiseaseStatus>(person_id);
+    if !matches!(disease_status, DiseaseStatus::I) {
+        return;
+    }
+    let interval = *context
+        .get_global_property_value::<RtInterval>()
+        .expect("Rt interval not specified");
+    let bin = (context.get_time() / interval).floor() as usize;
+    let infector_id = get_infector(context, person_id);
+    let state = context.get_data_container_mut::<RtPlugin>();
+    state.infection_bins.insert(person_id, bin);
+    state.bins.entry(bin).or_default().infectors += 1;
+    // Secondary infections are attributed to the bin in which their infector was infected
+    if let Some(infector_bin) =
+        infector_id.and_then(|infector_id| state.infection_bins.get(&infector_id).copied())
+    {
+        state
+            .bins
+            .entry(infector_bin)
+            .or_default()
+            .secondary_infections += 1;
+    }
+}
+
+/// Releases Rt for every bin; called once the simulation has finished, when every infector
+/// has completed their infectious period
+pub fn release_rt(context: &mut Context) {
+    let interval = *context
+        .get_global_property_value::<RtInterval>()
+        .expect("Rt interval not specified");
+    let bins = std::mem::take(&mut context.get_data_container_mut::<RtPlugin>().bins);
+    for (bin, counts) in bins {
+        context.release_report_item::<RtReport>(CaseReproductionNumber {
+            time: bin as f64 * interval,
+            infectors: counts.infectors,
+            secondary_infections: counts.secondary_infections,
+            rt: counts.secondary_infections as f64 / counts.infectors as f64,
+        });
+    }
+}
+
+impl Component for RtReport {
+    fn init(context: &mut Context) {
+        let interval = *context
+            .get_global_property_value::<RtInterval>()
+            .expect("Rt interval not specified");
+        assert!(interval > 0.0, "Rt interval must be positive");
+        context
+            .observe_person_property_changes::<DiseaseStatus>(handle_person_disease_status_change);
+    }
+}
@@ -0,0 +1,124 @@
+use std::collections::{BTreeMap, HashSet};
+
+use eosim::{
+    context::{Component, Context},
+    global_properties::GlobalPropertyContext,
+    people::PersonId,
+    person_properties::PersonPropertyContext,
+    reports::{Report, ReportsContext},
+};
+use serde_derive::{Deserialize, Serialize};
+
+use super::{
+    demography::get_births, global_properties::Population, person_properties::DiseaseStatus,
+    transmission_manager::get_infector,
+};
+
+pub struct SummaryReport {}
+
+#[derive(Serialize, Deserialize)]
+pub struct RunSummary {
+    pub attack_rate: f64,
+    pub total_infections: usize,
+    pub total_deaths: usize,
+    pub peak_incidence: usize,
+    pub peak_time: f64,
+    pub duration: f64,
+    pub realized_r0: f64,
+}
+
+impl Report for SummaryReport {
+    type Item = RunSummary;
+}
+
+#[derive(Default)]
+pub struct SummaryState {
+    total_infections: usize,
+    total_deaths: usize,
+    daily_incidence: BTreeMap<usize, usize>,
+    last_event_time: f64,
+    initial_infections: HashSet<PersonId>,
+    first_generation_infections: usize,
+}
+
+eosim::define_plugin!(SummaryPlugin, SummaryState, SummaryState::default());
+
+pub fn handle_person_disease_status_change(
+    context: &mut Context,
+    person_id: PersonId,
+    _: DiseaseStatus,
+) {
+    let time = context.get_time();
+    let disease_status = context.get_person_property_value::<DiseaseStatus>(person_id);
+    let infector_id = match disease_status {
+        DiseaseStatus::I => get_infector(context, person_id),
+        _ => None,
+    };
+    let state = context.get_data_container_mut::<SummaryPlugin>();
+    state.last_event_time = time;
+    match disease_status {
+        DiseaseStatus::I => {
+            state.total_infections += 1;
+            *state
+                .daily_incidence
+                .entry(time.floor() as usize)
+                .or_insert(0) += 1;
+            match infector_id {
+                None => {
+                    state.initial_infections.insert(person_id);
+                }
+                Some(infector_id) => {
+                    if state.initial_infections.contains(&infector_id) {
+                        state.first_generation_infections += 1;
+                    }
+                }
+            }
+        }
+        DiseaseStatus::D => state.total_deaths += 1,
+        _ => {}
+    }
+}
+
+/// Releases the summary of the whole run; called once the simulation has finished
+pub fn release_summary(context: &mut Context) {
+    // Everyone alive at some point in the run, including people born into it
+    let population = *context
+        .get_global_property_value::<Population>()
+        .expect("Population not specified")
+        + get_births(context);
+    let state = context.get_data_container_mut::<SummaryPlugin>();
+    // The earliest day wins ties for the peak
+    let (peak_day, peak_incidence) =
+        state
+            .daily_incidence
+            .iter()
+            .fold((0, 0), |peak, (day, incidence)| {
+                if *incidence > peak.1 {
+                    (*day, *incidence)
+                } else {
+                    peak
+                }
+            });
+    let summary = RunSummary {
+        attack_rate: state.total_infections as f64 / population as f64,
+        total_infections: state.total_infections,
+        total_deaths: state.total_deaths,
+        peak_incidence,
+        peak_time: peak_day as f64,
+        duration: state.last_event_time,
+        // Secondary infections caused by the initial infections, if there were any
+        realized_r0: if state.initial_infections.is_empty() {
+            0.0
+        } else {
+            state.first_generation_infections as f64 / state.initial_infections.len() as f64
+        },
+    };
+    context.release_report_item::<SummaryReport>(summary);
+}
+
+impl Component for SummaryReport {
+    fn init(context: &mut Context) {
+        context
+            .observe_person_property_changes::<DiseaseStatus>(handle_person_disease_status_change);
+    }
+}
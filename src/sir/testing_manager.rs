@@ -0,0 +1,147 @@
+use eosim::{
+    context::{Component, Context},
+    global_properties::GlobalPropertyContext,
+    people::PersonId,
+    person_properties::PersonPropertyContext,
+    random::RandomContext,
+    reports::ReportsContext,
+};
+use rand::Rng;
+use serde_derive::{Deserialize, Serialize};
+
+use super::{
+    demography::get_age,
+    global_properties::{Demography, Testing},
+    observed_case_report::{ObservedCase, ObservedCaseReport},
+    person_properties::DiseaseStatus,
+};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AscertainmentPeriod {
+    pub start: f64,
+    pub probability: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AgeAscertainment {
+    pub min_age: f64,
+    pub probability: f64,
+}
+
+/// Probability that an infection is tested, either constant, changing at given times or
+/// varying by age group
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum Ascertainment {
+    Constant(f64),
+    TimeVarying(Vec<AscertainmentPeriod>),
+    AgeSpecific(Vec<AgeAscertainment>),
+}
+
+impl Ascertainment {
+    /// `age` is only needed for age-specific ascertainment, which is checked at init
+    pub fn get_probability(&self, time: f64, age: Option<f64>) -> f64 {
+        match self {
+            Ascertainment::Constant(probability) => *probability,
+            // Infections before the first period are never ascertained
+            Ascertainment::TimeVarying(periods) => periods
+                .iter()
+                .rfind(|period| period.start <= time)
+                .map_or(0.0, |period| period.probability),
+            Ascertainment::AgeSpecific(age_groups) => age.map_or(0.0, |age| {
+                age_groups
+                    .iter()
+                    .rfind(|age_group| age_group.min_age <= age)
+                    .map_or(0.0, |age_group| age_group.probability)
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TestingParameters {
+    pub ascertainment: Ascertainment,
+    /// Time from infection to being tested
+    pub testing_delay: f64,
+    /// Time from being tested to the result being available
+    pub turnaround_delay: f64,
+    /// Probability that a test of an infected person is positive
+    pub sensitivity: f64,
+    /// Probability that a pending result is reported on each day of the week, starting at day 0
+    #[serde(default = "default_day_of_week_reporting")]
+    pub day_of_week_reporting: [f64; 7],
+}
+
+fn default_day_of_week_reporting() -> [f64; 7] {
+    [1.0; 7]
+}
+
+pub struct TestingManager {}
+
+impl Component for TestingManager {
+    fn init(context: &mut Context) {
+        let testing = context
+            .get_global_property_value::<Testing>()
+            .expect("Testing parameters not specified");
+        assert!(
+            testing.day_of_week_reporting.iter().any(|p| *p > 0.0),
+            "At least one day of the week must have reporting"
+        );
+        // Ages are only tracked by the demography model
+        assert!(
+            !matches!(testing.ascertainment, Ascertainment::AgeSpecific(_))
+                || context.get_global_property_value::<Demography>().is_some(),
+            "Age-specific ascertainment requires demography"
+        );
+        context
+            .observe_person_property_changes::<DiseaseStatus>(handle_person_disease_status_change);
+    }
+}
+
+eosim::define_random_id!(TestingRandomId);
+
+pub fn handle_person_disease_status_change(
+    context: &mut Context,
+    person_id: PersonId,
+    _: DiseaseStatus,
+) {
+    let disease_status = context.get_person_property_value::<DiseaseStatus>(person_id);
+    if matches!(disease_status, DiseaseStatus::I) {
+        schedule_test(context, person_id);
+    }
+}
+
+pub fn schedule_test(context: &mut Context, person_id: PersonId) {
+    let infection_time = context.get_time();
+    let age = get_age(context, person_id);
+    let testing = context
+        .get_global_property_value::<Testing>()
+        .expect("Testing parameters not specified")
+        .clone();
+    let mut rng = context.get_rng::<TestingRandomId>();
+    let tested = rng.gen::<f64>() < testing.ascertainment.get_probability(infection_time, age);
+    let positive = rng.gen::<f64>() < testing.sensitivity;
+    if !(tested && positive) {
+        return;
+    }
+
+    // Results are held until a day on which they are reported
+    let result_time = infection_time + testing.testing_delay + testing.turnaround_delay;
+    let result_day = result_time.floor() as usize;
+    let mut report_day = result_day;
+    while rng.gen::<f64>() >= testing.day_of_week_reporting[report_day % 7] {
+        report_day += 1;
+    }
+    drop(rng);
+    let report_time = if report_day == result_day {
+        result_time
+    } else {
+        report_day as f64
+    };
+    context.add_plan(report_time, move |context| {
+        context.release_report_item::<ObservedCaseReport>(ObservedCase {
+            time: context.get_time(),
+            infection_time,
+        })
+    });
+}
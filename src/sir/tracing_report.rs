@@ -0,0 +1,18 @@
+use eosim::reports::Report;
+use serde_derive::{Deserialize, Serialize};
+
+pub struct TracingReport {}
+
+#[derive(Serialize, Deserialize)]
+pub struct TracingAttempt {
+    pub time: f64,
+    pub index_case_id: usize,
+    pub contact_id: usize,
+    pub outcome: String,
+    pub infected: bool,
+    pub delay: Option<f64>,
+}
+
+impl Report for TracingReport {
+    type Item = TracingAttempt;
+}
@@ -11,21 +11,83 @@ use rand::Rng;
 use rand_distr::{Distribution, Exp};
 
 use super::{
-    global_properties::{InfectiousPeriod, Population, R0},
-    person_properties::DiseaseStatus,
+    behavior_manager::get_contact_multiplier,
+    global_properties::{InfectiousPeriod, Isolation, Population, R0},
+    person_properties::{DiseaseStatus, IsolationStatus, VitalStatus},
 };
 
 pub struct TransmissionManager {}
 
 impl Component for TransmissionManager {
     fn init(context: &mut Context) {
+        let population = *context
+            .get_global_property_value::<Population>()
+            .expect("Population not specified");
+        for id in 0..population {
+            add_contactable_person(context, PersonId::new(id));
+        }
         context
             .observe_person_property_changes::<DiseaseStatus>(handle_person_disease_status_change);
+        context.observe_person_property_changes::<IsolationStatus>(
+            handle_person_isolation_status_change,
+        );
+        context.observe_person_property_changes::<VitalStatus>(handle_person_vital_status_change);
+    }
+}
+
+/// Index of the people who can be contacted, supporting constant time removal
+pub struct ContactablePeople {
+    people: Vec<PersonId>,
+    positions: HashMap<PersonId, usize>,
+}
+
+impl ContactablePeople {
+    fn insert(&mut self, person_id: PersonId) {
+        if !self.positions.contains_key(&person_id) {
+            self.positions.insert(person_id, self.people.len());
+            self.people.push(person_id);
+        }
+    }
+
+    fn remove(&mut self, person_id: PersonId) {
+        if let Some(position) = self.positions.remove(&person_id) {
+            self.people.swap_remove(position);
+            if let Some(moved_person_id) = self.people.get(position) {
+                self.positions.insert(*moved_person_id, position);
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.people.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.people.is_empty()
     }
 }
 
 eosim::define_plugin!(TransmissionManagerPlugin, HashMap<PersonId, PlanId>, HashMap::new());
 
+// Contacts made by each currently infectious person, used for quarantine and tracing
+eosim::define_plugin!(
+    ContactHistoryPlugin,
+    HashMap<PersonId, Vec<PersonId>>,
+    HashMap::new()
+);
+
+eosim::define_plugin!(
+    ContactablePeoplePlugin,
+    ContactablePeople,
+    ContactablePeople {
+        people: Vec::new(),
+        positions: HashMap::new(),
+    }
+);
+
+// The person who infected each infected person; initial infections have no entry
+eosim::define_plugin!(InfectorPlugin, HashMap<PersonId, PersonId>, HashMap::new());
+
 eosim::define_random_id!(TransmissionRandomId);
 
 pub fn handle_person_disease_status_change(
@@ -36,21 +98,84 @@ pub fn handle_person_disease_status_change(
     let disease_status = context.get_person_property_value::<DiseaseStatus>(person_id);
     match disease_status {
         DiseaseStatus::I => schedule_next_infectious_contact(context, person_id),
-        DiseaseStatus::R | DiseaseStatus::D => cancel_next_infectious_contact(context, person_id),
+        DiseaseStatus::R => {
+            cancel_next_infectious_contact(context, person_id);
+            context
+                .get_data_container_mut::<ContactHistoryPlugin>()
+                .remove(&person_id);
+        }
+        DiseaseStatus::D => {
+            cancel_next_infectious_contact(context, person_id);
+            context
+                .get_data_container_mut::<ContactHistoryPlugin>()
+                .remove(&person_id);
+            remove_contactable_person(context, person_id);
+        }
         _ => {
-            println!("{}", context.get_time())
+            eprintln!("{}", context.get_time())
+        }
+    }
+}
+
+pub fn handle_person_isolation_status_change(
+    context: &mut Context,
+    person_id: PersonId,
+    _: IsolationStatus,
+) {
+    // Contacts are a Poisson process, so a pending contact can be redrawn at the new rate.
+    // People who made no contacts while fully isolated resume them once released.
+    let disease_status = context.get_person_property_value::<DiseaseStatus>(person_id);
+    let vital_status = context.get_person_property_value::<VitalStatus>(person_id);
+    if matches!(disease_status, DiseaseStatus::I) && matches!(vital_status, VitalStatus::Alive) {
+        cancel_next_infectious_contact(context, person_id);
+        schedule_next_infectious_contact(context, person_id);
+    }
+}
+
+pub fn handle_person_vital_status_change(
+    context: &mut Context,
+    person_id: PersonId,
+    _: VitalStatus,
+) {
+    let vital_status = context.get_person_property_value::<VitalStatus>(person_id);
+    if matches!(vital_status, VitalStatus::Dead) {
+        cancel_next_infectious_contact(context, person_id);
+        context
+            .get_data_container_mut::<ContactHistoryPlugin>()
+            .remove(&person_id);
+        remove_contactable_person(context, person_id);
+    }
+}
+
+fn get_contact_rate_multiplier(context: &mut Context, person_id: PersonId) -> f64 {
+    let isolation_status = context.get_person_property_value::<IsolationStatus>(person_id);
+    match isolation_status {
+        IsolationStatus::Free => 1.0,
+        IsolationStatus::Isolated | IsolationStatus::Quarantined => {
+            let contact_reduction = context
+                .get_global_property_value::<Isolation>()
+                .map_or(0.0, |isolation| isolation.contact_reduction);
+            1.0 - contact_reduction
         }
     }
 }
 
 fn schedule_next_infectious_contact(context: &mut Context, person_id: PersonId) {
-    let r0 = context
+    let r0 = *context
         .get_global_property_value::<R0>()
         .expect("R0 not specified");
-    let infectious_period = context
+    let infectious_period = *context
         .get_global_property_value::<InfectiousPeriod>()
         .expect("Infectious period not specified");
-    let contact_rate_dist = Exp::new(r0 / infectious_period).unwrap();
+    let contact_rate = r0 / infectious_period * get_contact_rate_multiplier(context, person_id);
+    if contact_rate <= 0.0 {
+        // Fully isolated people make no further contacts
+        context
+            .get_data_container_mut::<TransmissionManagerPlugin>()
+            .remove(&person_id);
+        return;
+    }
+    let contact_rate_dist = Exp::new(contact_rate).unwrap();
     let next_contact_time = context.get_time()
         + contact_rate_dist.sample(&mut *context.get_rng::<TransmissionRandomId>());
     let contact_plan = context.add_plan(next_contact_time, move |context| {
@@ -63,27 +188,61 @@ fn schedule_next_infectious_contact(context: &mut Context, person_id: PersonId)
 }
 
 fn attempt_infection(context: &mut Context, source_person_id: PersonId) {
-    let population = *context
-        .get_global_property_value::<Population>()
-        .expect("Population not specified");
-    if population > 1 {
-        let mut contact_id;
-        let mut rng = context.get_rng::<TransmissionRandomId>();
-        loop {
-            contact_id = PersonId::new((*rng).gen_range(0..population));
-            if contact_id != source_person_id {
-                break;
-            }
+    // Contacts avoided because of behavior change are thinned from the contact process
+    let contact_multiplier = get_contact_multiplier(context);
+    if contact_multiplier < 1.0
+        && context.get_rng::<TransmissionRandomId>().gen::<f64>() >= contact_multiplier
+    {
+        schedule_next_infectious_contact(context, source_person_id);
+        return;
+    }
+    if let Some(contact_id) = sample_contact(context, source_person_id) {
+        // Isolated and quarantined people also avoid the contacts others would make with them
+        let recipient_multiplier = get_contact_rate_multiplier(context, contact_id);
+        if recipient_multiplier < 1.0
+            && context.get_rng::<TransmissionRandomId>().gen::<f64>() >= recipient_multiplier
+        {
+            schedule_next_infectious_contact(context, source_person_id);
+            return;
         }
-        drop(rng);
+        context
+            .get_data_container_mut::<ContactHistoryPlugin>()
+            .entry(source_person_id)
+            .or_default()
+            .push(contact_id);
         let contact_disease_status = context.get_person_property_value::<DiseaseStatus>(contact_id);
         if matches!(contact_disease_status, DiseaseStatus::S) {
+            // Recorded first so that observers of the infection can look up the infector
+            context
+                .get_data_container_mut::<InfectorPlugin>()
+                .insert(contact_id, source_person_id);
             context.set_person_property_value::<DiseaseStatus>(contact_id, DiseaseStatus::I)
         }
         schedule_next_infectious_contact(context, source_person_id)
     }
 }
 
+/// Samples a contact for `source_person_id` uniformly from the living, contactable people
+fn sample_contact(context: &mut Context, source_person_id: PersonId) -> Option<PersonId> {
+    let contactable_count = context
+        .get_data_container_mut::<ContactablePeoplePlugin>()
+        .len();
+    if contactable_count <= 1 {
+        return None;
+    }
+    loop {
+        let index = context
+            .get_rng::<TransmissionRandomId>()
+            .gen_range(0..contactable_count);
+        let contact_id = context
+            .get_data_container_mut::<ContactablePeoplePlugin>()
+            .people[index];
+        if contact_id != source_person_id {
+            return Some(contact_id);
+        }
+    }
+}
+
 fn cancel_next_infectious_contact(context: &mut Context, person_id: PersonId) {
     let contact_plan = context
         .get_data_container_mut::<TransmissionManagerPlugin>()
@@ -92,3 +251,157 @@ fn cancel_next_infectious_contact(context: &mut Context, person_id: PersonId) {
         context.cancel_plan(contact_plan);
     }
 }
+
+pub fn get_contacts(context: &mut Context, person_id: PersonId) -> Vec<PersonId> {
+    context
+        .get_data_container_mut::<ContactHistoryPlugin>()
+        .get(&person_id)
+        .cloned()
+        .unwrap_or_default()
+}
+
+pub fn get_infector(context: &mut Context, person_id: PersonId) -> Option<PersonId> {
+    context
+        .get_data_container_mut::<InfectorPlugin>()
+        .get(&person_id)
+        .copied()
+}
+
+pub fn add_contactable_person(context: &mut Context, person_id: PersonId) {
+    context
+        .get_data_container_mut::<ContactablePeoplePlugin>()
+        .insert(person_id);
+}
+
+pub fn remove_contactable_person(context: &mut Context, person_id: PersonId) {
+    context
+        .get_data_container_mut::<ContactablePeoplePlugin>()
+        .remove(person_id);
+}
+
+#[cfg(test)]
+mod tests {
+    use eosim::{
+        context::Context, global_properties::GlobalPropertyContext, people::PersonId,
+        person_properties::PersonPropertyContext, random::RandomContext,
+    };
+
+    use super::*;
+    use crate::sir::{
+        death_manager::DeathManager,
+        global_properties::{DeathRate, InitialInfections},
+        infection_manager::InfectionManager,
+        infection_seeder::InfectionSeeder,
+        isolation_manager::{quarantine, IsolationParameters},
+        population_loader::PopulationLoader,
+    };
+
+    #[test]
+    fn test_dead_people_are_not_contacted() {
+        let population = 1000;
+        let mut context = Context::new();
+        context.set_global_property_value::<Population>(population);
+        context.set_global_property_value::<R0>(2.0);
+        context.set_global_property_value::<InfectiousPeriod>(4.0);
+        context.set_global_property_value::<InitialInfections>(50);
+        context.set_global_property_value::<DeathRate>(0.9);
+        context.set_base_random_seed(8675309);
+        context.add_component::<PopulationLoader>();
+        context.add_component::<InfectionManager>();
+        context.add_component::<TransmissionManager>();
+        context.add_component::<InfectionSeeder>();
+        context.add_component::<DeathManager>();
+        context.execute();
+
+        let dead = (0..population)
+            .map(PersonId::new)
+            .filter(|person_id| {
+                matches!(
+                    context.get_person_property_value::<DiseaseStatus>(*person_id),
+                    DiseaseStatus::D
+                )
+            })
+            .count();
+        assert!(dead > 0, "Expected some deaths with a high death rate");
+        assert_eq!(
+            context
+                .get_data_container_mut::<ContactablePeoplePlugin>()
+                .len(),
+            population - dead
+        );
+
+        let source_person_id = PersonId::new(0);
+        for _ in 0..10000 {
+            let contact_id = sample_contact(&mut context, source_person_id).unwrap();
+            assert!(!matches!(
+                context.get_person_property_value::<DiseaseStatus>(contact_id),
+                DiseaseStatus::D
+            ));
+        }
+    }
+
+    #[test]
+    fn test_contacts_resume_after_quarantine() {
+        let mut context = Context::new();
+        context.set_global_property_value::<Population>(100);
+        context.set_global_property_value::<R0>(100.0);
+        context.set_global_property_value::<InfectiousPeriod>(100.0);
+        context.set_global_property_value::<Isolation>(IsolationParameters {
+            detection_probability: 0.0,
+            detection_delay: 0.0,
+            contact_reduction: 1.0,
+            quarantine_contacts: false,
+            quarantine_duration: 1.0,
+        });
+        context.set_base_random_seed(8675309);
+        context.add_component::<PopulationLoader>();
+        context.add_component::<InfectionManager>();
+        context.add_component::<TransmissionManager>();
+
+        let person_id = PersonId::new(0);
+        context.set_person_property_value::<DiseaseStatus>(person_id, DiseaseStatus::I);
+        quarantine(&mut context, person_id);
+        context.add_plan(0.5, move |context| {
+            assert!(get_contacts(context, person_id).is_empty());
+        });
+        context.execute();
+
+        assert!((1..100).any(|id| get_infector(&mut context, PersonId::new(id)) == Some(person_id)));
+    }
+
+    #[test]
+    fn test_quarantined_people_are_not_infected() {
+        let population = 1000;
+        let mut context = Context::new();
+        context.set_global_property_value::<Population>(population);
+        context.set_global_property_value::<R0>(2.0);
+        context.set_global_property_value::<InfectiousPeriod>(4.0);
+        context.set_global_property_value::<InitialInfections>(50);
+        context.set_global_property_value::<Isolation>(IsolationParameters {
+            detection_probability: 0.0,
+            detection_delay: 0.0,
+            contact_reduction: 1.0,
+            quarantine_contacts: false,
+            quarantine_duration: 1000.0,
+        });
+        context.set_base_random_seed(8675309);
+        context.add_component::<PopulationLoader>();
+        for id in 0..population / 2 {
+            quarantine(&mut context, PersonId::new(id));
+        }
+        context.add_component::<InfectionManager>();
+        context.add_component::<TransmissionManager>();
+        context.add_component::<InfectionSeeder>();
+        context.execute();
+
+        let infected = (0..population)
+            .map(PersonId::new)
+            .filter(|person_id| get_infector(&mut context, *person_id).is_some())
+            .collect::<Vec<_>>();
+        assert!(!infected.is_empty(), "Expected some transmission");
+        // Infections are over long before quarantine is released
+        assert!(infected
+            .iter()
+            .all(|person_id| person_id.id() >= population / 2));
+    }
+}
@@ -0,0 +1,183 @@
+use std::fmt::Write;
+
+use eosim::{
+    context::{Component, Context},
+    people::PersonId,
+    person_properties::PersonPropertyContext,
+    random::RandomContext,
+    reports::{Report, ReportsContext},
+};
+use fxhash::FxHashMap;
+use rand::seq::index::sample;
+use serde_derive::{Deserialize, Serialize};
+
+use super::{person_properties::DiseaseStatus, transmission_manager::get_infector};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct TransmissionTreeParameters {
+    /// Number of chains, each descending from an initial infection, exported as Newick trees
+    #[serde(default)]
+    pub newick_sample_size: usize,
+}
+
+pub struct TransmissionTreeReport {}
+
+#[derive(Serialize, Deserialize)]
+pub struct TransmissionEdge {
+    pub time: f64,
+    pub infector_id: Option<usize>,
+    pub infectee_id: usize,
+    pub generation: usize,
+}
+
+impl Report for TransmissionTreeReport {
+    type Item = TransmissionEdge;
+}
+
+pub struct NewickTreeReport {}
+
+#[derive(Serialize, Deserialize)]
+pub struct NewickTree {
+    pub root_id: usize,
+    pub newick: String,
+}
+
+impl Report for NewickTreeReport {
+    type Item = NewickTree;
+}
+
+pub struct TreeNode {
+    time: f64,
+    generation: usize,
+    // Time since the infector was infected, which is the Newick branch length
+    branch_length: Option<f64>,
+    children: Vec<PersonId>,
+}
+
+#[derive(Default)]
+pub struct TransmissionTree {
+    nodes: FxHashMap<PersonId, TreeNode>,
+    roots: Vec<PersonId>,
+}
+
+eosim::define_plugin!(
+    TransmissionTreePlugin,
+    TransmissionTree,
+    TransmissionTree::default()
+);
+
+eosim::define_random_id!(TransmissionTreeRandomId);
+
+pub fn handle_person_disease_status_change(
+    context: &mut Context,
+    person_id: PersonId,
+    _: DiseaseStatus,
+) {
+    let disease_status = context.get_person_property_value::<DiseaseStatus>(person_id);
+    if !matches!(disease_status, DiseaseStatus::I) {
+        return;
+    }
+    let time = context.get_time();
+    let infector_id = get_infector(context, person_id);
+    let tree = context.get_data_container_mut::<TransmissionTreePlugin>();
+    let (generation, branch_length) =
+        match infector_id.and_then(|infector_id| tree.nodes.get_mut(&infector_id)) {
+            Some(infector) => {
+                infector.children.push(person_id);
+                (infector.generation + 1, Some(time - infector.time))
+            }
+            None => {
+                tree.roots.push(person_id);
+                (0, None)
+            }
+        };
+    tree.nodes.insert(
+        person_id,
+        TreeNode {
+            time,
+            generation,
+            branch_length,
+            children: Vec::new(),
+        },
+    );
+    context.release_report_item::<TransmissionTreeReport>(TransmissionEdge {
+        time,
+        infector_id: infector_id.map(|infector_id| infector_id.id()),
+        infectee_id: person_id.id(),
+        generation,
+    });
+}
+
+enum NewickStep {
+    Enter(PersonId),
+    Separator,
+    Exit(PersonId),
+}
+
+fn write_newick_label(output: &mut String, tree: &TransmissionTree, person_id: PersonId) {
+    write!(output, "{}", person_id.id()).unwrap();
+    if let Some(branch_length) = tree.nodes[&person_id].branch_length {
+        write!(output, ":{}", branch_length).unwrap();
+    }
+}
+
+/// Writes the chain descending from `root_id` in Newick format, without recursion so that
+/// long chains cannot overflow the stack
+fn get_newick(tree: &TransmissionTree, root_id: PersonId) -> String {
+    let mut output = String::new();
+    let mut steps = vec![NewickStep::Enter(root_id)];
+    while let Some(step) = steps.pop() {
+        match step {
+            NewickStep::Enter(person_id) => {
+                let children = &tree.nodes[&person_id].children;
+                if children.is_empty() {
+                    write_newick_label(&mut output, tree, person_id);
+                } else {
+                    output.push('(');
+                    steps.push(NewickStep::Exit(person_id));
+                    for (index, child_id) in children.iter().enumerate().rev() {
+                        steps.push(NewickStep::Enter(*child_id));
+                        if index > 0 {
+                            steps.push(NewickStep::Separator);
+                        }
+                    }
+                }
+            }
+            NewickStep::Separator => output.push(','),
+            NewickStep::Exit(person_id) => {
+                output.push(')');
+                write_newick_label(&mut output, tree, person_id);
+            }
+        }
+    }
+    output.push(';');
+    output
+}
+
+/// Releases Newick trees for a random sample of chains; called once the simulation has run
+pub fn release_newick_trees(context: &mut Context, sample_size: usize) {
+    let root_count = context
+        .get_data_container_mut::<TransmissionTreePlugin>()
+        .roots
+        .len();
+    let mut rng = context.get_rng::<TransmissionTreeRandomId>();
+    let mut root_indices = sample(&mut *rng, root_count, sample_size.min(root_count)).into_vec();
+    drop(rng);
+    root_indices.sort_unstable();
+    for root_index in root_indices {
+        let tree = context.get_data_container_mut::<TransmissionTreePlugin>();
+        let root_id = tree.roots[root_index];
+        let newick = get_newick(tree, root_id);
+        context.release_report_item::<NewickTreeReport>(NewickTree {
+            root_id: root_id.id(),
+            newick,
+        });
+    }
+}
+
+impl Component for TransmissionTreeReport {
+    fn init(context: &mut Context) {
+        context
+            .observe_person_property_changes::<DiseaseStatus>(handle_person_disease_status_change);
+    }
+}